@@ -1,3 +1,5 @@
+#![allow(clippy::approx_constant)]
+
 use docdb::{DocDb, DumpPolicy, SerializationMethod};
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
@@ -0,0 +1,155 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::{DocError, Result};
+use crate::index::canonical;
+use crate::iterator::DocDbIteratorItem;
+use crate::query::{compare, lookup, sort_order, Filter};
+use crate::serialization::Serializer;
+
+/// Aggregations over a set of documents, returned by [DocDb::aggregate](crate::DocDb::aggregate)
+/// and [Collection::aggregate](crate::Collection::aggregate).
+///
+/// Fields are dotted paths, as in a [Filter]. Documents are inspected as JSON documents,
+/// so every aggregation fails with a [DocError::Deserialization] if a value can't be
+/// read without knowing its type, and with a [DocError::Unsupported] with bincode.
+pub struct Aggregate<'a> {
+    serializer: &'a Serializer,
+    items: Vec<DocDbIteratorItem<'a>>,
+}
+
+impl<'a> Aggregate<'a> {
+    pub(crate) fn new(
+        serializer: &'a Serializer,
+        items: impl Iterator<Item = DocDbIteratorItem<'a>>,
+    ) -> Self {
+        Self {
+            serializer,
+            items: items.collect(),
+        }
+    }
+
+    fn docs(&self) -> Result<impl Iterator<Item = Result<(&DocDbIteratorItem<'a>, Value)>>> {
+        self.serializer.check_documents()?;
+        Ok(self
+            .items
+            .iter()
+            .map(|item| Ok((item, item.try_get_doc()?))))
+    }
+
+    /// Get the values of `field` in all the documents that have it
+    fn values(&self, field: &str) -> Result<Vec<Value>> {
+        let mut values = Vec::new();
+        for doc in self.docs()? {
+            let (_, doc) = doc?;
+            if let Some(val) = lookup(&doc, field) {
+                values.push(val.clone());
+            }
+        }
+        Ok(values)
+    }
+
+    fn numbers(&self, field: &str) -> Result<Vec<f64>> {
+        Ok(self
+            .values(field)?
+            .iter()
+            .filter_map(Value::as_f64)
+            .collect())
+    }
+
+    /// Get the number of documents.
+    pub fn count(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Get the number of documents that satisfy `filter`.
+    pub fn count_where(&self, filter: &Filter) -> Result<usize> {
+        let mut count = 0;
+        for doc in self.docs()? {
+            if filter.matches(&doc?.1) {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Get the sum of the numeric values of `field`. Documents where it is missing
+    /// or isn't a number are ignored.
+    pub fn sum(&self, field: &str) -> Result<f64> {
+        Ok(self.numbers(field)?.iter().sum())
+    }
+
+    /// Get the average of the numeric values of `field`, or `None` if there are none.
+    pub fn avg(&self, field: &str) -> Result<Option<f64>> {
+        let numbers = self.numbers(field)?;
+        if numbers.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(numbers.iter().sum::<f64>() / numbers.len() as f64))
+    }
+
+    /// Get the smallest value of `field`, or `None` if no document has it.
+    ///
+    /// Values are ordered like in a [Filter], where values of different kinds are
+    /// unordered, so the field should hold values of a single kind.
+    pub fn min<T: DeserializeOwned>(&self, field: &str) -> Result<Option<T>> {
+        self.extreme(field, Ordering::Less)
+    }
+
+    /// Get the largest value of `field`, or `None` if no document has it.
+    ///
+    /// Values are ordered like in a [Filter], where values of different kinds are
+    /// unordered, so the field should hold values of a single kind.
+    pub fn max<T: DeserializeOwned>(&self, field: &str) -> Result<Option<T>> {
+        self.extreme(field, Ordering::Greater)
+    }
+
+    fn extreme<T: DeserializeOwned>(&self, field: &str, wanted: Ordering) -> Result<Option<T>> {
+        let best = self
+            .values(field)?
+            .into_iter()
+            .reduce(|best, val| match compare(&val, &best) {
+                Some(ord) if ord == wanted => val,
+                _ => best,
+            });
+
+        best.map(|val| {
+            T::deserialize(val).map_err(|err| DocError::Deserialization(err.to_string()))
+        })
+        .transpose()
+    }
+
+    /// Group the documents by the value of `field`, ordered by that value.
+    ///
+    /// Documents that don't have the field are left out, and so are documents
+    /// whose value can't be deserialized into `K`.
+    pub fn group_by<K: DeserializeOwned>(
+        &self,
+        field: &str,
+    ) -> Result<Vec<(K, Vec<DocDbIteratorItem<'a>>)>> {
+        let mut groups: HashMap<String, (Value, Vec<DocDbIteratorItem<'a>>)> = HashMap::new();
+        for doc in self.docs()? {
+            let (item, doc) = doc?;
+            if let Some(val) = lookup(&doc, field) {
+                groups
+                    .entry(canonical(val))
+                    .or_insert_with(|| (val.clone(), Vec::new()))
+                    .1
+                    .push(item.clone());
+            }
+        }
+
+        let mut groups: Vec<_> = groups.into_values().collect();
+        groups.sort_by(|(a, _), (b, _)| sort_order(a, b));
+        for (_, items) in groups.iter_mut() {
+            items.sort_by_key(|item| item.key);
+        }
+        Ok(groups
+            .into_iter()
+            .filter_map(|(val, items)| Some((K::deserialize(val).ok()?, items)))
+            .collect())
+    }
+}
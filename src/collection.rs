@@ -0,0 +1,181 @@
+use std::sync::mpsc::Receiver;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::aggregate::Aggregate;
+use crate::db::DocDb;
+use crate::document::PatchOp;
+use crate::error::Result;
+use crate::id::Id;
+use crate::iterator::{DocDbIterator, DocDbIteratorItem};
+use crate::query::{Filter, Query};
+use crate::watch::Change;
+
+/// A handle to a named collection of a [DocDb], returned by [DocDb::collection].
+///
+/// All operations only see the keys of this collection. Changes are dumped
+/// according to the [DumpPolicy](crate::DumpPolicy) of the owning DB.
+pub struct Collection<'a> {
+    db: &'a mut DocDb,
+    name: String,
+}
+
+impl<'a> Collection<'a> {
+    pub(crate) fn new(db: &'a mut DocDb, name: &str) -> Self {
+        Self {
+            db,
+            name: name.to_string(),
+        }
+    }
+
+    /// Get the name of the collection.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set<T: Serialize>(&mut self, key: &str, val: &T) -> Result<()> {
+        self.db.set_in(Some(&self.name), key, val)
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.db.get_in(Some(&self.name), key)
+    }
+
+    pub(crate) fn try_get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.db.try_get_in(Some(&self.name), key)
+    }
+
+    /// Get the part of the value of `key` that the JSON Pointer `path` refers to.
+    pub fn get_path<T: DeserializeOwned>(&self, key: &str, path: &str) -> Option<T> {
+        self.db.get_path_in(Some(&self.name), key, path).ok()
+    }
+
+    /// Set the part of the value of `key` that the JSON Pointer `path` refers to.
+    pub fn set_path<T: Serialize>(&mut self, key: &str, path: &str, val: &T) -> Result<()> {
+        self.db.set_path_in(Some(&self.name), key, path, val)
+    }
+
+    /// Apply a JSON Merge Patch (RFC 7396) to the value of `key`.
+    pub fn merge_patch(&mut self, key: &str, patch: &serde_json::Value) -> Result<()> {
+        self.db.merge_patch_in(Some(&self.name), key, patch)
+    }
+
+    /// Apply a JSON Patch (RFC 6902) to the value of `key`, atomically.
+    pub fn apply_patch(&mut self, key: &str, ops: &[PatchOp]) -> Result<()> {
+        self.db.apply_patch_in(Some(&self.name), key, ops)
+    }
+
+    pub fn exist(&self, key: &str) -> bool {
+        self.db
+            .keyspace(Some(&self.name))
+            .is_some_and(|map| map.contains_key(key))
+    }
+
+    /// Get a vector of all the keys in the collection.
+    pub fn get_all_keys(&self) -> Vec<String> {
+        self.db
+            .keyspace(Some(&self.name))
+            .map(|map| map.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Get the total number of keys in the collection.
+    pub fn total_nums(&self) -> usize {
+        self.db
+            .keyspace(Some(&self.name))
+            .map_or(0, |map| map.len())
+    }
+
+    pub fn rem(&mut self, key: &str) -> Result<bool> {
+        self.db.rem_in(Some(&self.name), key)
+    }
+
+    /// Remove all the keys of the collection, keeping the collection itself.
+    pub fn clear(&mut self) -> Result<()> {
+        self.db.clear_in(Some(&self.name))
+    }
+
+    pub fn iter(&self) -> DocDbIterator<'_> {
+        self.db.iter_in(Some(&self.name))
+    }
+
+    /// Get all the values of the collection that satisfy `filter`.
+    pub fn find(&self, filter: &Filter) -> Result<Vec<DocDbIteratorItem<'_>>> {
+        self.db.find_in(Some(&self.name), filter)
+    }
+
+    /// Start a [Query] over the values of the collection.
+    pub fn query(&self) -> Query<'_> {
+        Query::new(self.db, Some(&self.name))
+    }
+
+    /// Get the aggregations over all the values of the collection.
+    pub fn aggregate(&self) -> Aggregate<'_> {
+        self.db.aggregate_in(Some(&self.name), "")
+    }
+
+    /// Get a receiver of the changes of the keys of the collection that start with `prefix`.
+    pub fn subscribe(&mut self, prefix: &str) -> Receiver<Change> {
+        self.db.subscribe_in(Some(&self.name), prefix)
+    }
+
+    /// Register a hook run before a key of the collection that starts with `prefix`
+    /// is set, see [DocDb::on_before_set].
+    pub fn on_before_set<F>(&mut self, prefix: &str, f: F)
+    where
+        F: Fn(&str, &mut serde_json::Value) -> Result<()> + Send + Sync + 'static,
+    {
+        self.db
+            .on_before_set_in(Some(&self.name), prefix, Box::new(f))
+    }
+
+    /// Register a hook run after a key of the collection that starts with `prefix`
+    /// is set, see [DocDb::on_after_set].
+    pub fn on_after_set<F>(&mut self, prefix: &str, f: F)
+    where
+        F: Fn(&str, &serde_json::Value) -> Result<()> + Send + Sync + 'static,
+    {
+        self.db
+            .on_after_set_in(Some(&self.name), prefix, Box::new(f))
+    }
+
+    /// Register a hook run before a key of the collection that starts with `prefix`
+    /// is removed, see [DocDb::on_before_rem].
+    pub fn on_before_rem<F>(&mut self, prefix: &str, f: F)
+    where
+        F: Fn(&str, &serde_json::Value) -> Result<()> + Send + Sync + 'static,
+    {
+        self.db
+            .on_before_rem_in(Some(&self.name), prefix, Box::new(f))
+    }
+
+    /// Attach the JSON Schema `schema` to the keys of the collection that start with
+    /// `prefix`, see [DocDb::set_schema]. An empty prefix applies it to the whole collection.
+    pub fn set_schema(&mut self, prefix: &str, schema: &serde_json::Value) -> Result<()> {
+        self.db.set_schema_in(Some(&self.name), prefix, schema)
+    }
+
+    /// Detach the JSON Schema attached to the keys of the collection that start with `prefix`.
+    pub fn remove_schema(&mut self, prefix: &str) -> Result<bool> {
+        self.db.remove_schema_in(Some(&self.name), prefix)
+    }
+
+    /// Store `val` under a newly generated [Id] and return it.
+    pub fn insert<T: Serialize>(&mut self, val: &T) -> Result<Id> {
+        let ids = self
+            .db
+            .insert_many_in(Some(&self.name), std::slice::from_ref(val))?;
+        Ok(ids[0])
+    }
+
+    /// Store each of `vals` under a newly generated [Id], dumping once for all of them.
+    pub fn insert_many<T: Serialize>(&mut self, vals: &[T]) -> Result<Vec<Id>> {
+        self.db.insert_many_in(Some(&self.name), vals)
+    }
+
+    /// Iterate over the values stored with [Collection::insert] in insertion order.
+    pub fn iter_inserted(&self) -> DocDbIterator<'_> {
+        self.db.iter_inserted_in(Some(&self.name))
+    }
+}
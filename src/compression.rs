@@ -0,0 +1,99 @@
+use crate::error::{DocError, Result};
+
+/// Magic bytes at the start of a compressed db file, followed by the id of the codec
+/// and, for Zstandard, the level as a little-endian `i32`. Files without them aren't
+/// compressed.
+const MAGIC: &[u8; 4] = b"DDBZ";
+
+#[cfg(feature = "zstd")]
+const ZSTD_ID: u8 = 1;
+#[cfg(feature = "lz4")]
+const LZ4_ID: u8 = 2;
+
+/// A codec to compress the db file with, see [DocDb::set_compression](crate::DocDb::set_compression)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Write the file as the serialization method produces it
+    #[default]
+    None,
+
+    /// [Zstandard compression](https://crates.io/crates/zstd) at the given level,
+    /// from 1 to 22, 0 being the default level
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+
+    /// [LZ4 compression](https://crates.io/crates/lz4_flex), faster than Zstandard
+    /// but with a lower ratio
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+/// Compress the content of a db file with `compression`, behind a header naming the codec
+pub(crate) fn compress(content: Vec<u8>, compression: Compression) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(content),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(level) => match zstd::encode_all(content.as_slice(), level) {
+            Ok(compressed) => Ok(with_header(
+                ZSTD_ID,
+                [&level.to_le_bytes()[..], &compressed].concat(),
+            )),
+            Err(err) => Err(DocError::Serialization(err.to_string())),
+        },
+        #[cfg(feature = "lz4")]
+        Compression::Lz4 => Ok(with_header(
+            LZ4_ID,
+            lz4_flex::compress_prepend_size(&content),
+        )),
+    }
+}
+
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+fn with_header(codec_id: u8, compressed: Vec<u8>) -> Vec<u8> {
+    [&MAGIC[..], &[codec_id], &compressed].concat()
+}
+
+/// Decompress the content of a db file, and tell which codec it was compressed with
+pub(crate) fn decompress(content: Vec<u8>) -> Result<(Vec<u8>, Compression)> {
+    if !content.starts_with(MAGIC) {
+        return Ok((content, Compression::None));
+    }
+
+    #[allow(unused_variables)]
+    let compressed = content.get(MAGIC.len() + 1..).unwrap_or_default();
+    #[allow(unreachable_patterns)]
+    match content.get(MAGIC.len()) {
+        #[cfg(feature = "zstd")]
+        Some(&ZSTD_ID) => {
+            let (level, compressed) = match compressed.split_first_chunk::<4>() {
+                Some((level, compressed)) => (i32::from_le_bytes(*level), compressed),
+                None => {
+                    return Err(DocError::Deserialization(
+                        "the db file has a truncated zstd header".to_string(),
+                    ))
+                }
+            };
+            match zstd::decode_all(compressed) {
+                Ok(decompressed) => Ok((decompressed, Compression::Zstd(level))),
+                Err(err) => Err(DocError::Deserialization(err.to_string())),
+            }
+        }
+        #[cfg(feature = "lz4")]
+        Some(&LZ4_ID) => match lz4_flex::decompress_size_prepended(compressed) {
+            Ok(decompressed) => Ok((decompressed, Compression::Lz4)),
+            Err(err) => Err(DocError::Deserialization(err.to_string())),
+        },
+        Some(1) => Err(codec_err("zstd")),
+        Some(2) => Err(codec_err("lz4")),
+        _ => Err(DocError::Deserialization(
+            "the db file is compressed with an unknown codec".to_string(),
+        )),
+    }
+}
+
+fn codec_err(feature: &str) -> DocError {
+    DocError::Deserialization(format!(
+        "the db file is compressed with {}, which needs the `{}` feature",
+        feature, feature
+    ))
+}
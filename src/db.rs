@@ -1,13 +1,39 @@
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+use crate::aggregate::Aggregate;
+use crate::collection::Collection;
+use crate::compression::{self, Compression};
+use crate::document::{self, PatchOp};
+#[cfg(feature = "encryption")]
+use crate::encryption::{self, Cipher};
 use crate::error::{DocError, Result};
-use crate::iterator::DocDbIterator;
-use crate::serialization::{SerializationMethod, Serializer};
+#[cfg(feature = "fts")]
+use crate::fts::TextIndex;
+use crate::hooks::{self, Hook, Hooks};
+use crate::id::Id;
+#[cfg(feature = "fts")]
+use crate::index::TextIndexDef;
+#[cfg(feature = "vector")]
+use crate::index::VectorIndexDef;
+use crate::index::{Index, IndexDef};
+use crate::iterator::{DocDbIterator, DocDbIteratorItem};
+use crate::query::{Filter, Query};
+use crate::schema::{self, SchemaDef};
+use crate::serialization::{
+    DbData, DbMap, DocSerializer, SerializationMethod, Serializer, SerializerOptions,
+};
+use crate::table::Table;
+#[cfg(feature = "vector")]
+use crate::vector::{self, Metric, VectorIndex};
+use crate::watch::{Change, ChangeKind, Subscriber};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::sync::mpsc::{self, Receiver};
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::{
-    collections::HashMap,
     path::{Path, PathBuf},
     time::{Duration, Instant},
 };
@@ -21,10 +47,28 @@ pub enum DumpPolicy {
     PeriodicDump(Duration),
 }
 
+/// A migration of the stored data to the next schema version, see
+/// [DocDb::load_with_migrations]
+pub type Migration = fn(&mut DocDb) -> Result<()>;
+
 pub struct DocDb {
-    ///
-    map: HashMap<String, Vec<u8>>,
+    data: DbData,
+    /// secondary indexes by collection name, built from `data.indexes`
+    indexes: HashMap<String, Vec<Index>>,
+    /// full-text indexes by collection name, built from `data.text_indexes`
+    #[cfg(feature = "fts")]
+    text_indexes: HashMap<String, TextIndex>,
+    /// vector indexes by collection name, built from `data.vector_indexes`
+    #[cfg(feature = "vector")]
+    vector_indexes: HashMap<String, Vec<VectorIndex>>,
     serializer: Serializer,
+    compression: Compression,
+    /// encrypts the db file, see `set_encryption_key`
+    #[cfg(feature = "encryption")]
+    cipher: Option<Cipher>,
+    /// receivers of the changes made by `subscribe`
+    subscribers: Vec<Subscriber>,
+    hooks: Hooks,
     db_file_path: PathBuf,
     dump_policy: DumpPolicy,
     last_dump: Instant,
@@ -35,15 +79,42 @@ impl DocDb {
         db_path: P,
         dump_policy: DumpPolicy,
         serialize_method: SerializationMethod,
+    ) -> Self {
+        Self::new_with(db_path, dump_policy, Serializer::new(serialize_method))
+    }
+
+    /// Create a DB like [DocDb::new], written in a format of the user's own
+    pub fn with_serializer<P: AsRef<Path>>(
+        db_path: P,
+        dump_policy: DumpPolicy,
+        serializer: Box<dyn DocSerializer>,
+    ) -> Self {
+        Self::new_with(db_path, dump_policy, Serializer::custom(serializer))
+    }
+
+    fn new_with<P: AsRef<Path>>(
+        db_path: P,
+        dump_policy: DumpPolicy,
+        serializer: Serializer,
     ) -> Self {
         let mut path_buf = PathBuf::new();
         path_buf.push(db_path);
 
         Self {
-            map: HashMap::new(),
-            serializer: Serializer::new(serialize_method),
+            data: DbData::default(),
+            indexes: HashMap::new(),
+            #[cfg(feature = "fts")]
+            text_indexes: HashMap::new(),
+            #[cfg(feature = "vector")]
+            vector_indexes: HashMap::new(),
+            serializer,
+            compression: Compression::None,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+            subscribers: Vec::new(),
+            hooks: Hooks::default(),
             db_file_path: path_buf,
-            dump_policy: dump_policy,
+            dump_policy,
             last_dump: Instant::now(),
         }
     }
@@ -58,6 +129,26 @@ impl DocDb {
         DocDb::new(db_path, dump_policy, SerializationMethod::Yaml)
     }
 
+    #[cfg(feature = "toml")]
+    pub fn new_toml<P: AsRef<Path>>(db_path: P, dump_policy: DumpPolicy) -> Self {
+        DocDb::new(db_path, dump_policy, SerializationMethod::Toml)
+    }
+
+    #[cfg(feature = "ron")]
+    pub fn new_ron<P: AsRef<Path>>(db_path: P, dump_policy: DumpPolicy) -> Self {
+        DocDb::new(db_path, dump_policy, SerializationMethod::Ron)
+    }
+
+    #[cfg(feature = "cbor")]
+    pub fn new_cbor<P: AsRef<Path>>(db_path: P, dump_policy: DumpPolicy) -> Self {
+        DocDb::new(db_path, dump_policy, SerializationMethod::Cbor)
+    }
+
+    #[cfg(feature = "msgpack")]
+    pub fn new_msgpack<P: AsRef<Path>>(db_path: P, dump_policy: DumpPolicy) -> Self {
+        DocDb::new(db_path, dump_policy, SerializationMethod::MessagePack)
+    }
+
     #[cfg(feature = "bincode")]
     pub fn new_bincode<P: AsRef<Path>>(db_path: P, dump_policy: DumpPolicy) -> Self {
         DocDb::new(db_path, dump_policy, SerializationMethod::Bin)
@@ -67,29 +158,186 @@ impl DocDb {
         db_path: P,
         dump_policy: DumpPolicy,
         ser_method: SerializationMethod,
+    ) -> Result<DocDb> {
+        Self::load_with(db_path, dump_policy, Serializer::new(ser_method))
+    }
+
+    /// Load a DB like [DocDb::load], from a file written by `serializer`,
+    /// see [DocDb::with_serializer]
+    pub fn load_with_serializer<P: AsRef<Path>>(
+        db_path: P,
+        dump_policy: DumpPolicy,
+        serializer: Box<dyn DocSerializer>,
+    ) -> Result<DocDb> {
+        Self::load_with(db_path, dump_policy, Serializer::custom(serializer))
+    }
+
+    /// Load a DB like [DocDb::load], from a file encrypted with `key`, and keep
+    /// encrypting it, see [DocDb::set_encryption_key].
+    ///
+    /// Fails with [DocError::Decryption] if the file isn't encrypted, if the key is
+    /// wrong or if the file was tampered with.
+    #[cfg(feature = "encryption")]
+    pub fn load_encrypted<P: AsRef<Path>>(
+        db_path: P,
+        dump_policy: DumpPolicy,
+        ser_method: SerializationMethod,
+        key: &[u8; 32],
     ) -> Result<DocDb> {
         let content = match fs::read(db_path.as_ref()) {
             Ok(file_content) => file_content,
             Err(err) => return Err(DocError::IO(err)),
         };
 
-        let serializer = Serializer::new(ser_method);
+        let cipher = Cipher::new(key);
+        let content = cipher.decrypt(&content)?;
+        let mut db =
+            Self::from_content(db_path, content, dump_policy, Serializer::new(ser_method))?;
+        db.cipher = Some(cipher);
+        Ok(db)
+    }
 
-        let maps_from_file = match serializer.deserialize_db(&content) {
-            Ok(maps) => maps,
-            Err(err) => return Err(err),
+    fn load_with<P: AsRef<Path>>(
+        db_path: P,
+        dump_policy: DumpPolicy,
+        serializer: Serializer,
+    ) -> Result<DocDb> {
+        let content = match fs::read(db_path.as_ref()) {
+            Ok(file_content) => file_content,
+            Err(err) => return Err(DocError::IO(err)),
         };
+        #[cfg(feature = "encryption")]
+        if encryption::is_encrypted(&content) {
+            return Err(DocError::Decryption(
+                "the db file is encrypted, it must be loaded with its key".to_string(),
+            ));
+        }
+
+        Self::from_content(db_path, content, dump_policy, serializer)
+    }
+
+    /// Build a DB from the content of its file
+    fn from_content<P: AsRef<Path>>(
+        db_path: P,
+        content: Vec<u8>,
+        dump_policy: DumpPolicy,
+        mut serializer: Serializer,
+    ) -> Result<DocDb> {
+        let (content, compression) = compression::decompress(content)?;
+
+        let data_from_file = serializer.deserialize_db(&content)?;
+        serializer.options = data_from_file.options;
 
         let mut db_path_buf = PathBuf::new();
         db_path_buf.push(db_path);
 
-        Ok(DocDb {
-            map: maps_from_file,
+        let mut db = DocDb {
+            data: DbData::default(),
+            indexes: HashMap::new(),
+            #[cfg(feature = "fts")]
+            text_indexes: HashMap::new(),
+            #[cfg(feature = "vector")]
+            vector_indexes: HashMap::new(),
             serializer,
+            compression,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+            subscribers: Vec::new(),
+            hooks: Hooks::default(),
             db_file_path: db_path_buf,
             dump_policy,
             last_dump: Instant::now(),
-        })
+        };
+
+        let empty = DbMap::new();
+        for def in data_from_file.indexes.iter() {
+            let map = data_from_file.collections.get(&def.collection);
+            let index = Index::build(def.clone(), map.unwrap_or(&empty), &db.serializer)?;
+            db.indexes
+                .entry(def.collection.clone())
+                .or_default()
+                .push(index);
+        }
+        #[cfg(feature = "fts")]
+        for def in data_from_file.text_indexes.iter() {
+            let map = data_from_file.collections.get(&def.collection);
+            let index = TextIndex::build(def.clone(), map.unwrap_or(&empty), &db.serializer)?;
+            db.text_indexes.insert(def.collection.clone(), index);
+        }
+        #[cfg(feature = "vector")]
+        for def in data_from_file.vector_indexes.iter() {
+            let map = data_from_file.collections.get(&def.collection);
+            let index = VectorIndex::build(def.clone(), map.unwrap_or(&empty), &db.serializer)?;
+            db.vector_indexes
+                .entry(def.collection.clone())
+                .or_default()
+                .push(index);
+        }
+        db.data = data_from_file;
+
+        Ok(db)
+    }
+
+    /// Load a DB like [DocDb::load], then upgrade its data to the latest schema version.
+    ///
+    /// Each migration `(version, f)` upgrades the data of the previous version to
+    /// `version`. The migrations whose version is higher than the stored one are run
+    /// in order of version, then the version of the last one is stored and the DB is
+    /// dumped, unless the dump policy is [DumpPolicy::NeverDump]. Nothing is dumped
+    /// while the migrations run, so if one of them fails the file is left unchanged
+    /// and its error is returned.
+    ///
+    /// The schema version of a DB created by [DocDb::new] is 0, see
+    /// [DocDb::set_schema_version].
+    pub fn load_with_migrations<P: AsRef<Path>>(
+        db_path: P,
+        dump_policy: DumpPolicy,
+        ser_method: SerializationMethod,
+        migrations: &[(u64, Migration)],
+    ) -> Result<DocDb> {
+        let mut db = DocDb::load(db_path, DumpPolicy::NeverDump, ser_method)?;
+
+        let mut migrations = migrations.to_vec();
+        migrations.sort_by_key(|(version, _)| *version);
+        let mut migrated = false;
+        for (version, migration) in migrations {
+            if version > db.data.schema_version {
+                migration(&mut db)?;
+                db.data.schema_version = version;
+                migrated = true;
+            }
+        }
+
+        db.dump_policy = dump_policy;
+        if migrated {
+            if let Err(err) = db.dump() {
+                // don't let the drop of the db write the migrated data either
+                db.dump_policy = DumpPolicy::NeverDump;
+                return Err(err);
+            }
+        }
+        Ok(db)
+    }
+
+    /// Get the schema version of the stored data.
+    pub fn schema_version(&self) -> u64 {
+        self.data.schema_version
+    }
+
+    /// Set the schema version of the stored data, e.g. to mark a new DB as holding
+    /// data of the latest version so that no migration is run when it is loaded.
+    pub fn set_schema_version(&mut self, version: u64) -> Result<()> {
+        let original = self.data.schema_version;
+        self.data.schema_version = version;
+
+        match self.dump_now() {
+            Ok(_) => Ok(()),
+            // dump failed, restore the version
+            Err(err) => {
+                self.data.schema_version = original;
+                Err(err)
+            }
+        }
     }
 
     pub fn load_read_only<P: AsRef<Path>>(
@@ -109,17 +357,138 @@ impl DocDb {
         Self::load(db_path, dump_policy, SerializationMethod::Yaml)
     }
 
+    #[cfg(feature = "toml")]
+    pub fn load_toml<P: AsRef<Path>>(db_path: P, dump_policy: DumpPolicy) -> Result<Self> {
+        Self::load(db_path, dump_policy, SerializationMethod::Toml)
+    }
+
+    #[cfg(feature = "ron")]
+    pub fn load_ron<P: AsRef<Path>>(db_path: P, dump_policy: DumpPolicy) -> Result<Self> {
+        Self::load(db_path, dump_policy, SerializationMethod::Ron)
+    }
+
+    #[cfg(feature = "cbor")]
+    pub fn load_cbor<P: AsRef<Path>>(db_path: P, dump_policy: DumpPolicy) -> Result<Self> {
+        Self::load(db_path, dump_policy, SerializationMethod::Cbor)
+    }
+
+    #[cfg(feature = "msgpack")]
+    pub fn load_msgpack<P: AsRef<Path>>(db_path: P, dump_policy: DumpPolicy) -> Result<Self> {
+        Self::load(db_path, dump_policy, SerializationMethod::MessagePack)
+    }
+
     #[cfg(feature = "bincode")]
     pub fn load_bin<P: AsRef<Path>>(db_path: P, dump_policy: DumpPolicy) -> Result<Self> {
         Self::load(db_path, dump_policy, SerializationMethod::Bin)
     }
 
+    /// Compress the db file with `compression` from the next dump on.
+    ///
+    /// The file starts with a header naming the codec and its level, so [DocDb::load]
+    /// finds out whether and how a file is compressed by itself, and a loaded DB keeps
+    /// compressing its file the same way.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Encrypt the db file with `key` from the next dump on, or stop encrypting it
+    /// for `None`.
+    ///
+    /// The file is encrypted with XChaCha20-Poly1305, which also authenticates it:
+    /// loading it with a wrong key or after it was tampered with fails, see
+    /// [DocDb::load_encrypted]. Only the file is encrypted, not the DB in memory.
+    #[cfg(feature = "encryption")]
+    pub fn set_encryption_key(&mut self, key: Option<&[u8; 32]>) {
+        self.cipher = key.map(Cipher::new);
+    }
+
+    fn encrypt(&self, content: Vec<u8>) -> Result<Vec<u8>> {
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = &self.cipher {
+            return cipher.encrypt(&content);
+        }
+        Ok(content)
+    }
+
+    /// Convert the DB file at `src_path`, written with `src_method`, into a DB file
+    /// at `dst_path` written with `dst_method`, see [DocDb::change_serialization].
+    pub fn convert<P: AsRef<Path>, Q: AsRef<Path>>(
+        src_path: P,
+        src_method: SerializationMethod,
+        dst_path: Q,
+        dst_method: SerializationMethod,
+    ) -> Result<()> {
+        let mut db = DocDb::load(src_path, DumpPolicy::DumpRelyRequest, src_method)?;
+        db.db_file_path = dst_path.as_ref().to_path_buf();
+        db.change_serialization(dst_method)?;
+        db.dump()
+    }
+
+    /// Re-encode every stored value with `ser_method`, which is used from now on.
+    ///
+    /// Values are converted as JSON documents, without knowing their Rust types, so
+    /// this fails with a [DocError::Unsupported] if the DB is in bincode or `ser_method`
+    /// is bincode: bincode values can only be read back as the exact type they were
+    /// written from, which a struct turned into a map, or an `Option`, no longer is.
+    pub fn change_serialization(&mut self, ser_method: SerializationMethod) -> Result<()> {
+        let mut serializer = Serializer::new(ser_method);
+        serializer.check_documents()?;
+        serializer.options = self.serializer.options;
+
+        // raw values are kept as they are
+        let convert = |map: &DbMap, raw_keys: &HashSet<String>| -> Result<DbMap> {
+            map.iter()
+                .map(|(key, ser_data)| {
+                    if raw_keys.contains(key) {
+                        return Ok((key.to_string(), ser_data.clone()));
+                    }
+                    let doc = self.serializer.try_deserialize_doc(ser_data)?;
+                    Ok((key.to_string(), serializer.serialize_data(&doc)?))
+                })
+                .collect()
+        };
+        let mut map = convert(&self.data.map, &self.data.raw_keys)?;
+        let mut collections = HashMap::new();
+        for (name, coll) in self.data.collections.iter() {
+            collections.insert(name.to_string(), convert(coll, &HashSet::new())?);
+        }
+
+        std::mem::swap(&mut self.data.map, &mut map);
+        std::mem::swap(&mut self.data.collections, &mut collections);
+        let original = std::mem::replace(&mut self.serializer, serializer);
+
+        match self.dump_now() {
+            Ok(_) => Ok(()),
+            // dump failed, restore the values and the serializer
+            Err(err) => {
+                self.data.map = map;
+                self.data.collections = collections;
+                self.serializer = original;
+                Err(err)
+            }
+        }
+    }
+
+    /// Set how the db file is written from the next dump on, e.g. pretty-printed
+    /// and with sorted keys so that it can be kept in version control.
+    ///
+    /// The options are stored in the file, so they don't need to be set again
+    /// after loading it.
+    pub fn set_serializer_options(&mut self, options: SerializerOptions) {
+        self.serializer.options = options;
+    }
+
     pub fn dump(&mut self) -> Result<()> {
         if let DumpPolicy::NeverDump = self.dump_policy {
             return Ok(());
         }
 
-        match self.serializer.serialize_db(&self.map) {
+        match self
+            .serializer
+            .serialize_db(&self.data)
+            .and_then(|ser_data| compression::compress(ser_data, self.compression))
+            .and_then(|ser_data| self.encrypt(ser_data))
+        {
             Ok(ser_data) => {
                 let temp_file_path = format!(
                     "{}.temp.{}",
@@ -167,38 +536,100 @@ impl DocDb {
     }
 
     pub fn set<T: Serialize>(&mut self, key: &str, val: &T) -> Result<()> {
-        let ser_data = match self.serializer.serialize_data(val) {
-            Ok(data) => data,
-            Err(err) => return Err(err),
-        };
+        self.set_in(None, key, val)
+    }
 
-        let original_val = self.map.insert(key.to_string(), ser_data);
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.get_in(None, key)
+    }
+
+    /// Store `val` under `key` as is, without serializing it, e.g. to keep an opaque blob.
+    ///
+    /// Raw values are written to the file as bytes, or as base64 strings in the text
+    /// formats. [DocDb::get] and the document methods only read them if they happen
+    /// to hold data in the serialization method of the DB, and [DocDb::find],
+    /// [DocDb::query] and [DocDb::aggregate] skip them.
+    ///
+    /// Hooks see the value as a base64 string. A raw value can't be checked against
+    /// a schema, so setting one under a key that has a schema fails with
+    /// [DocError::SchemaViolation].
+    pub fn set_raw(&mut self, key: &str, val: &[u8]) -> Result<()> {
+        if self.data.schemas.iter().any(|def| def.applies(None, key)) {
+            return Err(DocError::SchemaViolation {
+                path: String::new(),
+                message: "a raw value can't be checked against the schema of its key".to_string(),
+            });
+        }
+
+        let val = self.run_before_set(None, key, val.to_vec(), true)?;
+        let original_val = self.put(None, key, val)?;
+        let was_raw = !self.data.raw_keys.insert(key.to_string());
+        if let Err(err) = self.run_after_set(None, key) {
+            self.restore(None, key, original_val);
+            if !was_raw {
+                self.data.raw_keys.remove(key);
+            }
+            return Err(err);
+        }
 
         match self.dump_now() {
-            Ok(_) => Ok(()),
-            // set value failed, need to roll back
+            Ok(_) => {
+                self.notify(None, key, ChangeKind::Set, original_val);
+                Ok(())
+            }
+            // dump failed, restore the previous value
             Err(err) => {
-                match original_val {
-                    // not exist before insert
-                    None => self.map.remove(key),
-                    // exist and reset old val
-                    Some(orig_val) => self.map.insert(String::from(key), orig_val.to_vec()),
-                };
-
+                self.restore(None, key, original_val);
+                if !was_raw {
+                    self.data.raw_keys.remove(key);
+                }
                 Err(err)
             }
         }
     }
 
-    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
-        match self.map.get(key) {
-            Some(v) => self.serializer.deserialize_data(v),
-            None => None,
-        }
+    /// Get the value of `key` as it is stored: serialized, or as given to [DocDb::set_raw].
+    pub fn get_raw(&self, key: &str) -> Option<&[u8]> {
+        self.data.map.get(key).map(Vec::as_slice)
     }
 
     pub fn exist(&self, key: &str) -> bool {
-        self.map.get(key).is_some()
+        self.data.map.contains_key(key)
+    }
+
+    /// Get the part of the value of `key` that the JSON Pointer `path` refers to,
+    /// e.g. `"/server/port"`.
+    ///
+    /// Like [DocDb::get], returns `None` if the key or path doesn't exist or the
+    /// value can't be deserialized into `T`. Paths require self-describing values,
+    /// so this always returns `None` with bincode.
+    pub fn get_path<T: DeserializeOwned>(&self, key: &str, path: &str) -> Option<T> {
+        self.get_path_in(None, key, path).ok()
+    }
+
+    /// Set the part of the value of `key` that the JSON Pointer `path` refers to.
+    ///
+    /// A missing object member is added, but its parent must exist. Fails with
+    /// [DocError::Path] if the key or the parent doesn't exist, and with
+    /// [DocError::Unsupported] with bincode.
+    pub fn set_path<T: Serialize>(&mut self, key: &str, path: &str, val: &T) -> Result<()> {
+        self.set_path_in(None, key, path, val)
+    }
+
+    /// Apply a JSON Merge Patch (RFC 7396) to the value of `key`: the members of
+    /// `patch` replace those of the value recursively, and `null` members remove them.
+    ///
+    /// Fails with [DocError::Path] if the key doesn't exist.
+    pub fn merge_patch(&mut self, key: &str, patch: &serde_json::Value) -> Result<()> {
+        self.merge_patch_in(None, key, patch)
+    }
+
+    /// Apply a JSON Patch (RFC 6902) to the value of `key`.
+    ///
+    /// The operations are applied atomically: if any of them fails, e.g. a `test`
+    /// operation with [DocError::Patch], the value is left unchanged.
+    pub fn apply_patch(&mut self, key: &str, ops: &[PatchOp]) -> Result<()> {
+        self.apply_patch_in(None, key, ops)
     }
 
     /// Get a vector of all the keys in the DB.
@@ -206,35 +637,1282 @@ impl DocDb {
     /// The keys returned in the vector are not references to the actual key string
     /// objects but rather a clone of them.
     pub fn get_all_keys(&self) -> Vec<String> {
-        self.map.keys().cloned().collect()
+        self.data.map.keys().cloned().collect()
     }
 
     /// Get the total number of keys in the DB.
     pub fn total_nums(&self) -> usize {
-        self.map.len()
+        self.data.map.len()
     }
 
     pub fn rem(&mut self, key: &str) -> Result<bool> {
-        let remove_map = match self.map.remove(key) {
-            // exists key, return old value and dump db now
-            Some(v) => match self.dump_now() {
-                // dump successfully, return some(v)
-                Ok(_) => Some(v),
-                // dump failed, restore key in map
-                Err(err) => {
-                    self.map.insert(key.to_string(), v);
-                    return Err(err);
-                }
-            },
-            None => None,
+        self.rem_in(None, key)
+    }
+
+    pub fn iter(&self) -> DocDbIterator<'_> {
+        DocDbIterator {
+            map_iter: Box::new(self.data.map.iter()),
+            serializer: &self.serializer,
+        }
+    }
+
+    /// Store `val` under a newly generated [Id] and return it.
+    pub fn insert<T: Serialize>(&mut self, val: &T) -> Result<Id> {
+        let ids = self.insert_many_in(None, std::slice::from_ref(val))?;
+        Ok(ids[0])
+    }
+
+    /// Store each of `vals` under a newly generated [Id], dumping once for all of them.
+    ///
+    /// If the dump fails none of the values are stored.
+    pub fn insert_many<T: Serialize>(&mut self, vals: &[T]) -> Result<Vec<Id>> {
+        self.insert_many_in(None, vals)
+    }
+
+    /// Iterate over the values stored with [DocDb::insert] in insertion order.
+    ///
+    /// Keys set directly with [DocDb::set] are skipped unless they look like an [Id].
+    pub fn iter_inserted(&self) -> DocDbIterator<'_> {
+        self.iter_inserted_in(None)
+    }
+
+    /// Get all the top-level values that satisfy `filter`.
+    ///
+    /// Values are inspected as JSON documents, so this fails with a
+    /// [DocError::Deserialization] if a value can't be read without knowing its type,
+    /// and with a [DocError::Unsupported] with bincode.
+    pub fn find(&self, filter: &Filter) -> Result<Vec<DocDbIteratorItem<'_>>> {
+        self.find_in(None, filter)
+    }
+
+    /// Start a [Query] over the top-level values.
+    pub fn query(&self) -> Query<'_> {
+        Query::new(self, None)
+    }
+
+    /// Get the aggregations over the top-level values whose key starts with `prefix`.
+    /// An empty prefix selects all of them.
+    pub fn aggregate(&self, prefix: &str) -> Aggregate<'_> {
+        self.aggregate_in(None, prefix)
+    }
+
+    /// Get a handle to the collection `name`.
+    ///
+    /// A collection is a keyspace of its own, isolated from the top-level keys and
+    /// from other collections, and is stored in the same file as the rest of the DB.
+    /// It is created by the first write to it; reading a collection that doesn't
+    /// exist finds it empty.
+    pub fn collection(&mut self, name: &str) -> Collection<'_> {
+        Collection::new(self, name)
+    }
+
+    /// Get a typed handle to the collection `name`, see [DocDb::collection].
+    ///
+    /// Unlike [DocDb::get], reading a value that isn't a `T` through the returned
+    /// [Table] is reported as an error.
+    pub fn table<T>(&mut self, name: &str) -> Table<'_, T>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        Table::new(self.collection(name))
+    }
+
+    /// Get a receiver of the changes of the top-level keys that start with `prefix`.
+    ///
+    /// A [Change] is sent for every key set or removed, once the change is applied.
+    /// Dropping the receiver ends the subscription.
+    pub fn subscribe(&mut self, prefix: &str) -> Receiver<Change> {
+        self.subscribe_in(None, prefix)
+    }
+
+    /// Register a hook run by every write of a top-level key that starts with `prefix`,
+    /// before the value is stored.
+    ///
+    /// The hook gets the key and the value as a JSON document, which it may modify,
+    /// e.g. to maintain an `updated_at` field. Returning an error, typically
+    /// [DocError::Rejected], cancels the write. Hooks run on `set`, `insert` and the
+    /// writes built on them, but not when keys are removed in bulk by
+    /// [Collection::clear] or [DocDb::drop_collection]. Values are handed to hooks as
+    /// JSON documents, so a write that triggers a hook fails with bincode.
+    pub fn on_before_set<F>(&mut self, prefix: &str, f: F)
+    where
+        F: Fn(&str, &mut serde_json::Value) -> Result<()> + Send + Sync + 'static,
+    {
+        self.on_before_set_in(None, prefix, Box::new(f))
+    }
+
+    /// Register a hook run by every write of a top-level key that starts with `prefix`,
+    /// once the value is stored but before it is dumped.
+    ///
+    /// Returning an error rolls the write back.
+    pub fn on_after_set<F>(&mut self, prefix: &str, f: F)
+    where
+        F: Fn(&str, &serde_json::Value) -> Result<()> + Send + Sync + 'static,
+    {
+        self.on_after_set_in(None, prefix, Box::new(f))
+    }
+
+    /// Register a hook run before a top-level key that starts with `prefix` is removed,
+    /// with the value being removed.
+    ///
+    /// Returning an error cancels the removal.
+    pub fn on_before_rem<F>(&mut self, prefix: &str, f: F)
+    where
+        F: Fn(&str, &serde_json::Value) -> Result<()> + Send + Sync + 'static,
+    {
+        self.on_before_rem_in(None, prefix, Box::new(f))
+    }
+
+    /// Attach the JSON Schema `schema` to the top-level keys that start with `prefix`,
+    /// replacing the schema already attached to that prefix.
+    ///
+    /// Every write of such a key then fails with [DocError::SchemaViolation], naming the
+    /// failing path, if the value doesn't conform to the schema. When several prefixes
+    /// match a key, the value must conform to all of their schemas. Values already
+    /// stored aren't checked. The schema is stored in the db file.
+    ///
+    /// The validation keywords of JSON Schema are supported except `pattern`,
+    /// `patternProperties`, `format`, `$ref` and the conditional and `contains`
+    /// keywords: a schema using them anywhere fails with [DocError::Query]. Values are
+    /// validated as JSON documents, so a write that has a schema fails with bincode.
+    pub fn set_schema(&mut self, prefix: &str, schema: &serde_json::Value) -> Result<()> {
+        self.set_schema_in(None, prefix, schema)
+    }
+
+    /// Detach the JSON Schema attached to the top-level keys that start with `prefix`.
+    ///
+    /// Returns `false` if there is none.
+    pub fn remove_schema(&mut self, prefix: &str) -> Result<bool> {
+        self.remove_schema_in(None, prefix)
+    }
+
+    /// Get the names of all the collections in the DB.
+    pub fn list_collections(&self) -> Vec<String> {
+        self.data.collections.keys().cloned().collect()
+    }
+
+    /// Remove the collection `name` together with all of its keys and indexes.
+    ///
+    /// Returns `false` if no such collection exists.
+    pub fn drop_collection(&mut self, name: &str) -> Result<bool> {
+        let map = match self.data.collections.remove(name) {
+            Some(map) => map,
+            None => return Ok(false),
+        };
+        let indexes = self.indexes.remove(name);
+        #[cfg(feature = "fts")]
+        let text_index = self.text_indexes.remove(name);
+        #[cfg(feature = "vector")]
+        let vector_indexes = self.vector_indexes.remove(name);
+        let original_defs = self.data.indexes.clone();
+        let original_text_defs = self.data.text_indexes.clone();
+        let original_vector_defs = self.data.vector_indexes.clone();
+        self.data.indexes.retain(|def| def.collection != name);
+        self.data.text_indexes.retain(|def| def.collection != name);
+        self.data
+            .vector_indexes
+            .retain(|def| def.collection != name);
+
+        match self.dump_now() {
+            Ok(_) => {
+                for (key, val) in map {
+                    self.notify(Some(name), &key, ChangeKind::Removed, Some(val));
+                }
+                Ok(true)
+            }
+            // dump failed, restore the collection
+            Err(err) => {
+                self.data.collections.insert(name.to_string(), map);
+                if let Some(indexes) = indexes {
+                    self.indexes.insert(name.to_string(), indexes);
+                }
+                #[cfg(feature = "fts")]
+                if let Some(text_index) = text_index {
+                    self.text_indexes.insert(name.to_string(), text_index);
+                }
+                #[cfg(feature = "vector")]
+                if let Some(vector_indexes) = vector_indexes {
+                    self.vector_indexes.insert(name.to_string(), vector_indexes);
+                }
+                self.data.indexes = original_defs;
+                self.data.text_indexes = original_text_defs;
+                self.data.vector_indexes = original_vector_defs;
+                Err(err)
+            }
+        }
+    }
+
+    /// Create an index of `field` over the documents of the collection `collection`.
+    ///
+    /// `field` is a dotted path as used by [Filter]. The index is kept up to date by
+    /// every write to the collection and is used by `find` for equality filters on
+    /// the field. Its definition is stored in the db file and the index is rebuilt
+    /// on load. Indexing requires self-describing values, so it fails with bincode.
+    pub fn create_index(&mut self, collection: &str, field: &str) -> Result<()> {
+        self.add_index(IndexDef {
+            collection: collection.to_string(),
+            field: field.to_string(),
+            unique: false,
+        })
+    }
+
+    /// Like [DocDb::create_index], but a write that would give two documents the
+    /// same value of `field` fails with [DocError::UniqueViolation].
+    ///
+    /// Fails with the same error if the existing documents already share a value.
+    pub fn create_unique_index(&mut self, collection: &str, field: &str) -> Result<()> {
+        self.add_index(IndexDef {
+            collection: collection.to_string(),
+            field: field.to_string(),
+            unique: true,
+        })
+    }
+
+    /// Remove the index of `field` from the collection `collection`.
+    ///
+    /// Returns `false` if no such index exists.
+    pub fn drop_index(&mut self, collection: &str, field: &str) -> Result<bool> {
+        let pos = match self
+            .data
+            .indexes
+            .iter()
+            .position(|def| def.collection == collection && def.field == field)
+        {
+            Some(pos) => pos,
+            None => return Ok(false),
         };
+        let def = self.data.indexes.remove(pos);
+        let indexes = self.indexes.entry(collection.to_string()).or_default();
+        let index_pos = indexes.iter().position(|index| index.def == def);
+        let index = index_pos.map(|pos| indexes.remove(pos));
+
+        match self.dump_now() {
+            Ok(_) => Ok(true),
+            // dump failed, restore the index
+            Err(err) => {
+                self.data.indexes.insert(pos, def);
+                if let Some(index) = index {
+                    self.indexes
+                        .entry(collection.to_string())
+                        .or_default()
+                        .push(index);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn add_index(&mut self, def: IndexDef) -> Result<()> {
+        if self.data.indexes.contains(&def) {
+            return Ok(());
+        }
+
+        let map = self
+            .data
+            .collections
+            .entry(def.collection.clone())
+            .or_default();
+        let index = Index::build(def.clone(), map, &self.serializer)?;
+
+        // an index of the same field that differs in uniqueness is replaced
+        let original_defs = self.data.indexes.clone();
+        self.data
+            .indexes
+            .retain(|d| d.collection != def.collection || d.field != def.field);
+        self.data.indexes.push(def.clone());
 
-        Ok(remove_map.is_some())
+        let indexes = self.indexes.entry(def.collection.clone()).or_default();
+        let replaced = indexes
+            .iter()
+            .position(|index| index.def.field == def.field)
+            .map(|pos| indexes.remove(pos));
+        indexes.push(index);
+
+        match self.dump_now() {
+            Ok(_) => Ok(()),
+            // dump failed, restore the previous indexes
+            Err(err) => {
+                self.data.indexes = original_defs;
+                let indexes = self.indexes.entry(def.collection.clone()).or_default();
+                indexes.retain(|index| index.def != def);
+                indexes.extend(replaced);
+                Err(err)
+            }
+        }
     }
 
-    pub fn iter(&self) -> DocDbIterator {
+    /// Create a full-text index over the string `fields` of the documents of the
+    /// collection `collection`, replacing its current one if there is one.
+    ///
+    /// Fields are dotted paths as used by [Filter]; a field holding an array of
+    /// strings has all of them indexed. Text is split into terms at every character
+    /// that isn't alphanumeric and lowercased. Like [DocDb::create_index], the index
+    /// is kept up to date by every write, its definition is stored in the db file and
+    /// it fails with bincode.
+    #[cfg(feature = "fts")]
+    pub fn create_text_index(&mut self, collection: &str, fields: &[&str]) -> Result<()> {
+        let def = TextIndexDef {
+            collection: collection.to_string(),
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+        };
+        let map = self
+            .data
+            .collections
+            .entry(collection.to_string())
+            .or_default();
+        let index = TextIndex::build(def.clone(), map, &self.serializer)?;
+
+        let original_defs = self.data.text_indexes.clone();
+        self.data
+            .text_indexes
+            .retain(|d| d.collection != collection);
+        self.data.text_indexes.push(def);
+        let replaced = self.text_indexes.insert(collection.to_string(), index);
+
+        match self.dump_now() {
+            Ok(_) => Ok(()),
+            // dump failed, restore the previous index
+            Err(err) => {
+                self.data.text_indexes = original_defs;
+                match replaced {
+                    Some(index) => self.text_indexes.insert(collection.to_string(), index),
+                    None => self.text_indexes.remove(collection),
+                };
+                Err(err)
+            }
+        }
+    }
+
+    /// Remove the full-text index of the collection `collection`.
+    ///
+    /// Returns `false` if it has none.
+    #[cfg(feature = "fts")]
+    pub fn drop_text_index(&mut self, collection: &str) -> Result<bool> {
+        let pos = match self
+            .data
+            .text_indexes
+            .iter()
+            .position(|def| def.collection == collection)
+        {
+            Some(pos) => pos,
+            None => return Ok(false),
+        };
+        let def = self.data.text_indexes.remove(pos);
+        let index = self.text_indexes.remove(collection);
+
+        match self.dump_now() {
+            Ok(_) => Ok(true),
+            // dump failed, restore the index
+            Err(err) => {
+                self.data.text_indexes.insert(pos, def);
+                if let Some(index) = index {
+                    self.text_indexes.insert(collection.to_string(), index);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Search the full-text index of the collection `collection` for the terms of
+    /// `query`, and get the keys of the documents holding any of them, best match first.
+    ///
+    /// Fails with [DocError::Query] if the collection has no full-text index.
+    #[cfg(feature = "fts")]
+    pub fn search(&self, collection: &str, query: &str) -> Result<Vec<String>> {
+        let index = self.text_indexes.get(collection).ok_or_else(|| {
+            DocError::Query(format!("collection {} has no text index", collection))
+        })?;
+        let total = self.keyspace(Some(collection)).map_or(0, |map| map.len());
+        Ok(index.search(query, total))
+    }
+
+    /// Create an approximate nearest-neighbor index of the vector `field` of the
+    /// documents of the collection `collection`, used by [DocDb::nearest_approx].
+    ///
+    /// The vectors are partitioned into `lists` lists around centroids trained with
+    /// k-means on the current documents, and a query only scores the vectors of the
+    /// `probes` lists closest to it: more probes are slower but more accurate.
+    /// Vectors written later join their closest list, so an index built over very
+    /// different data should be recreated. Like [DocDb::create_index], the index is
+    /// kept up to date by every write, its definition is stored in the db file and it
+    /// fails with bincode.
+    #[cfg(feature = "vector")]
+    pub fn create_vector_index(
+        &mut self,
+        collection: &str,
+        field: &str,
+        lists: usize,
+        probes: usize,
+    ) -> Result<()> {
+        let def = VectorIndexDef {
+            collection: collection.to_string(),
+            field: field.to_string(),
+            lists: lists.max(1),
+            probes: probes.max(1),
+        };
+        let map = self
+            .data
+            .collections
+            .entry(collection.to_string())
+            .or_default();
+        let index = VectorIndex::build(def.clone(), map, &self.serializer)?;
+
+        let original_defs = self.data.vector_indexes.clone();
+        self.data
+            .vector_indexes
+            .retain(|d| d.collection != collection || d.field != field);
+        self.data.vector_indexes.push(def);
+        let indexes = self
+            .vector_indexes
+            .entry(collection.to_string())
+            .or_default();
+        let replaced = indexes
+            .iter()
+            .position(|index| index.def.field == field)
+            .map(|pos| indexes.remove(pos));
+        indexes.push(index);
+
+        match self.dump_now() {
+            Ok(_) => Ok(()),
+            // dump failed, restore the previous index
+            Err(err) => {
+                self.data.vector_indexes = original_defs;
+                let indexes = self
+                    .vector_indexes
+                    .entry(collection.to_string())
+                    .or_default();
+                indexes.retain(|index| index.def.field != field);
+                indexes.extend(replaced);
+                Err(err)
+            }
+        }
+    }
+
+    /// Remove the vector index of `field` from the collection `collection`.
+    ///
+    /// Returns `false` if no such index exists.
+    #[cfg(feature = "vector")]
+    pub fn drop_vector_index(&mut self, collection: &str, field: &str) -> Result<bool> {
+        let pos = match self
+            .data
+            .vector_indexes
+            .iter()
+            .position(|def| def.collection == collection && def.field == field)
+        {
+            Some(pos) => pos,
+            None => return Ok(false),
+        };
+        let def = self.data.vector_indexes.remove(pos);
+        let indexes = self
+            .vector_indexes
+            .entry(collection.to_string())
+            .or_default();
+        let index = indexes
+            .iter()
+            .position(|index| index.def.field == field)
+            .map(|pos| indexes.remove(pos));
+
+        match self.dump_now() {
+            Ok(_) => Ok(true),
+            // dump failed, restore the index
+            Err(err) => {
+                self.data.vector_indexes.insert(pos, def);
+                if let Some(index) = index {
+                    self.vector_indexes
+                        .entry(collection.to_string())
+                        .or_default()
+                        .push(index);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Get the keys of the `k` documents of the collection `collection` whose vector
+    /// `field` is the closest to `query` according to `metric`, with their scores,
+    /// closest first.
+    ///
+    /// This compares `query` to every vector of the collection. Documents whose field
+    /// isn't an array of numbers of the same length as `query` are left out. Values
+    /// are inspected as JSON documents, so this fails with bincode.
+    #[cfg(feature = "vector")]
+    pub fn nearest(
+        &self,
+        collection: &str,
+        field: &str,
+        k: usize,
+        query: &[f32],
+        metric: Metric,
+    ) -> Result<Vec<(String, f32)>> {
+        vector::check_query(query, k)?;
+        let mut scored = Vec::new();
+        for item in self.iter_in(Some(collection)) {
+            let doc = item.try_get_doc()?;
+            match vector::embedding(&doc, field) {
+                Some(v) if v.len() == query.len() => {
+                    scored.push((item.key.to_string(), metric.score(query, &v)))
+                }
+                _ => continue,
+            }
+        }
+        Ok(metric.rank(scored, k))
+    }
+
+    /// Like [DocDb::nearest], but only compares `query` to the vectors of the lists
+    /// of the index created by [DocDb::create_vector_index] that are the closest to it,
+    /// so the result may miss some of the closest documents.
+    ///
+    /// Fails with [DocError::Query] if `field` has no vector index.
+    #[cfg(feature = "vector")]
+    pub fn nearest_approx(
+        &self,
+        collection: &str,
+        field: &str,
+        k: usize,
+        query: &[f32],
+        metric: Metric,
+    ) -> Result<Vec<(String, f32)>> {
+        vector::check_query(query, k)?;
+        let index = self
+            .vector_indexes
+            .get(collection)
+            .and_then(|indexes| indexes.iter().find(|index| index.def.field == field))
+            .ok_or_else(|| {
+                DocError::Query(format!("{}.{} has no vector index", collection, field))
+            })?;
+        Ok(index.nearest(k, query, metric))
+    }
+
+    /// Get the keyspace of `collection`, or the top-level keyspace for `None`.
+    pub(crate) fn keyspace(&self, collection: Option<&str>) -> Option<&DbMap> {
+        match collection {
+            None => Some(&self.data.map),
+            Some(name) => self.data.collections.get(name),
+        }
+    }
+
+    fn keyspace_mut(&mut self, collection: Option<&str>) -> &mut DbMap {
+        match collection {
+            None => &mut self.data.map,
+            Some(name) => self.data.collections.entry(name.to_string()).or_default(),
+        }
+    }
+
+    pub(crate) fn set_in<T: Serialize>(
+        &mut self,
+        collection: Option<&str>,
+        key: &str,
+        val: &T,
+    ) -> Result<()> {
+        let ser_data = self.serializer.serialize_data(val)?;
+        let ser_data = self.run_before_set(collection, key, ser_data, false)?;
+        self.check_schemas(collection, key, &ser_data)?;
+
+        let original_val = self.put(collection, key, ser_data)?;
+        let was_raw = self.unmark_raw(collection, key);
+        if let Err(err) = self.run_after_set(collection, key) {
+            self.restore(collection, key, original_val);
+            self.remark_raw(key, was_raw);
+            return Err(err);
+        }
+
+        match self.dump_now() {
+            Ok(_) => {
+                self.notify(collection, key, ChangeKind::Set, original_val);
+                Ok(())
+            }
+            // set value failed, need to roll back
+            Err(err) => {
+                self.restore(collection, key, original_val);
+                self.remark_raw(key, was_raw);
+                Err(err)
+            }
+        }
+    }
+
+    pub(crate) fn on_before_set_in(
+        &mut self,
+        collection: Option<&str>,
+        prefix: &str,
+        f: Box<hooks::BeforeSet>,
+    ) {
+        self.hooks.before_set.push(Hook::new(collection, prefix, f));
+    }
+
+    pub(crate) fn on_after_set_in(
+        &mut self,
+        collection: Option<&str>,
+        prefix: &str,
+        f: Box<hooks::Check>,
+    ) {
+        self.hooks.after_set.push(Hook::new(collection, prefix, f));
+    }
+
+    pub(crate) fn on_before_rem_in(
+        &mut self,
+        collection: Option<&str>,
+        prefix: &str,
+        f: Box<hooks::Check>,
+    ) {
+        self.hooks.before_rem.push(Hook::new(collection, prefix, f));
+    }
+
+    pub(crate) fn set_schema_in(
+        &mut self,
+        collection: Option<&str>,
+        prefix: &str,
+        schema: &serde_json::Value,
+    ) -> Result<()> {
+        schema::check_schema(schema)?;
+        let def = SchemaDef {
+            collection: collection.map(str::to_string),
+            prefix: prefix.to_string(),
+            schema: schema.to_string(),
+        };
+
+        let original_defs = self.data.schemas.clone();
+        self.data
+            .schemas
+            .retain(|d| d.collection != def.collection || d.prefix != def.prefix);
+        self.data.schemas.push(def);
+
+        match self.dump_now() {
+            Ok(_) => Ok(()),
+            // dump failed, restore the previous schemas
+            Err(err) => {
+                self.data.schemas = original_defs;
+                Err(err)
+            }
+        }
+    }
+
+    pub(crate) fn remove_schema_in(
+        &mut self,
+        collection: Option<&str>,
+        prefix: &str,
+    ) -> Result<bool> {
+        let pos = match self
+            .data
+            .schemas
+            .iter()
+            .position(|def| def.collection.as_deref() == collection && def.prefix == prefix)
+        {
+            Some(pos) => pos,
+            None => return Ok(false),
+        };
+        let def = self.data.schemas.remove(pos);
+
+        match self.dump_now() {
+            Ok(_) => Ok(true),
+            // dump failed, restore the schema
+            Err(err) => {
+                self.data.schemas.insert(pos, def);
+                Err(err)
+            }
+        }
+    }
+
+    /// Validate `ser_data` against the schemas attached to `key`
+    fn check_schemas(&self, collection: Option<&str>, key: &str, ser_data: &[u8]) -> Result<()> {
+        let mut schemas = self
+            .data
+            .schemas
+            .iter()
+            .filter(|def| def.applies(collection, key))
+            .peekable();
+        if schemas.peek().is_none() {
+            return Ok(());
+        }
+
+        let doc = self.serializer.try_deserialize_doc(ser_data)?;
+        for def in schemas {
+            let schema: serde_json::Value = serde_json::from_str(&def.schema)?;
+            schema::validate(&schema, &doc)?;
+        }
+        Ok(())
+    }
+
+    /// Run the `on_before_set` hooks of `key` over `ser_data` and get the value to store.
+    /// `raw` tells whether `ser_data` is a value given to `set_raw`.
+    fn run_before_set(
+        &self,
+        collection: Option<&str>,
+        key: &str,
+        ser_data: Vec<u8>,
+        raw: bool,
+    ) -> Result<Vec<u8>> {
+        let mut hooks = hooks::matching(&self.hooks.before_set, collection, key).peekable();
+        if hooks.peek().is_none() {
+            return Ok(ser_data);
+        }
+
+        let mut doc = self.hook_doc(&ser_data, raw)?;
+        for hook in hooks {
+            hook(key, &mut doc)?;
+        }
+        if !raw {
+            return self.serializer.serialize_data(&doc);
+        }
+        match doc.as_str().map(|encoded| BASE64.decode(encoded)) {
+            Some(Ok(val)) => Ok(val),
+            _ => Err(DocError::Serialization(
+                "a hook turned a raw value into something other than a base64 string".to_string(),
+            )),
+        }
+    }
+
+    /// Get the document the hooks see for `ser_data`: raw values are given as a
+    /// base64 string, as in the text formats
+    fn hook_doc(&self, ser_data: &[u8], raw: bool) -> Result<serde_json::Value> {
+        if raw {
+            return Ok(serde_json::Value::String(BASE64.encode(ser_data)));
+        }
+        self.serializer.try_deserialize_doc(ser_data)
+    }
+
+    /// Run the `on_after_set` hooks of `key` over its stored value
+    fn run_after_set(&self, collection: Option<&str>, key: &str) -> Result<()> {
+        self.run_checks(&self.hooks.after_set, collection, key)
+    }
+
+    /// Run `hooks` over the current value of `key`, if it has one
+    fn run_checks(
+        &self,
+        hooks: &[Hook<hooks::Check>],
+        collection: Option<&str>,
+        key: &str,
+    ) -> Result<()> {
+        let mut hooks = hooks::matching(hooks, collection, key).peekable();
+        if hooks.peek().is_none() {
+            return Ok(());
+        }
+
+        let doc = match self.keyspace(collection).and_then(|map| map.get(key)) {
+            Some(ser_data) => self.hook_doc(ser_data, self.is_raw(collection, key))?,
+            None => return Ok(()),
+        };
+        for hook in hooks {
+            hook(key, &doc)?;
+        }
+        Ok(())
+    }
+
+    /// Store `ser_data` under `key` and update the indexes of `collection`.
+    ///
+    /// Returns the previous value of `key`.
+    fn put(
+        &mut self,
+        collection: Option<&str>,
+        key: &str,
+        ser_data: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>> {
+        self.update_indexes(collection, key, Some(&ser_data))?;
+        Ok(self
+            .keyspace_mut(collection)
+            .insert(key.to_string(), ser_data))
+    }
+
+    /// Remove `key` and update the indexes of `collection`.
+    ///
+    /// Returns the removed value.
+    fn take(&mut self, collection: Option<&str>, key: &str) -> Result<Option<Vec<u8>>> {
+        // don't create the collection just to find the key missing
+        if !self
+            .keyspace(collection)
+            .is_some_and(|map| map.contains_key(key))
+        {
+            return Ok(None);
+        }
+        self.update_indexes(collection, key, None)?;
+        Ok(self.keyspace_mut(collection).remove(key))
+    }
+
+    /// Undo a `put` or `take` of `key` by writing back its previous value
+    fn restore(&mut self, collection: Option<&str>, key: &str, previous: Option<Vec<u8>>) {
+        // the previous state was consistent with the indexes, so this can't fail
+        let _ = match previous {
+            Some(v) => self.put(collection, key, v),
+            None => self.take(collection, key),
+        };
+    }
+
+    /// Check whether `key` holds a value stored by `set_raw`
+    fn is_raw(&self, collection: Option<&str>, key: &str) -> bool {
+        collection.is_none() && self.data.raw_keys.contains(key)
+    }
+
+    /// Forget that `key` holds a raw value, see [DocDb::set_raw].
+    ///
+    /// Returns whether it did, to be passed to `remark_raw` when the change is undone.
+    fn unmark_raw(&mut self, collection: Option<&str>, key: &str) -> bool {
+        collection.is_none() && self.data.raw_keys.remove(key)
+    }
+
+    fn remark_raw(&mut self, key: &str, was_raw: bool) {
+        if was_raw {
+            self.data.raw_keys.insert(key.to_string());
+        }
+    }
+
+    /// Check whether the collection `name` has any index to keep up to date
+    fn is_indexed(&self, name: &str) -> bool {
+        #[cfg(feature = "fts")]
+        if self.text_indexes.contains_key(name) {
+            return true;
+        }
+        #[cfg(feature = "vector")]
+        if self
+            .vector_indexes
+            .get(name)
+            .is_some_and(|indexes| !indexes.is_empty())
+        {
+            return true;
+        }
+        self.indexes
+            .get(name)
+            .is_some_and(|indexes| !indexes.is_empty())
+    }
+
+    /// Replace the current value of `key` in the indexes of `collection` by `new_val`
+    fn update_indexes(
+        &mut self,
+        collection: Option<&str>,
+        key: &str,
+        new_val: Option<&[u8]>,
+    ) -> Result<()> {
+        let name = match collection {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        if !self.is_indexed(name) {
+            return Ok(());
+        }
+
+        let old_doc = match self.data.collections.get(name).and_then(|map| map.get(key)) {
+            Some(v) => Some(self.serializer.try_deserialize_doc(v)?),
+            None => None,
+        };
+        let new_doc = match new_val {
+            Some(v) => Some(self.serializer.try_deserialize_doc(v)?),
+            None => None,
+        };
+
+        let indexes = self.indexes.entry(name.to_string()).or_default();
+        if let Some(doc) = &new_doc {
+            for index in indexes.iter() {
+                index.check(key, doc)?;
+            }
+        }
+        for index in indexes.iter_mut() {
+            if let Some(doc) = &old_doc {
+                index.remove(key, doc);
+            }
+            if let Some(doc) = &new_doc {
+                index.insert(key, doc);
+            }
+        }
+        #[cfg(feature = "fts")]
+        if let Some(index) = self.text_indexes.get_mut(name) {
+            if let Some(doc) = &old_doc {
+                index.remove(key, doc);
+            }
+            if let Some(doc) = &new_doc {
+                index.insert(key, doc);
+            }
+        }
+        #[cfg(feature = "vector")]
+        for index in self.vector_indexes.get_mut(name).into_iter().flatten() {
+            index.remove(key);
+            if let Some(doc) = &new_doc {
+                index.insert(key, doc);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get_in<T: DeserializeOwned>(
+        &self,
+        collection: Option<&str>,
+        key: &str,
+    ) -> Option<T> {
+        match self.keyspace(collection)?.get(key) {
+            Some(v) => self.serializer.deserialize_data(v),
+            None => None,
+        }
+    }
+
+    /// Like `get_in`, but a value that can't be deserialized into `T` is an error
+    pub(crate) fn try_get_in<T: DeserializeOwned>(
+        &self,
+        collection: Option<&str>,
+        key: &str,
+    ) -> Result<Option<T>> {
+        match self.keyspace(collection).and_then(|map| map.get(key)) {
+            Some(v) => self.serializer.try_deserialize_data(v).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn get_path_in<T: DeserializeOwned>(
+        &self,
+        collection: Option<&str>,
+        key: &str,
+        path: &str,
+    ) -> Result<T> {
+        let doc = self.get_doc_in(collection, key)?;
+        let val = document::get(&doc, path)?;
+        T::deserialize(val).map_err(|err| DocError::Deserialization(err.to_string()))
+    }
+
+    pub(crate) fn set_path_in<T: Serialize>(
+        &mut self,
+        collection: Option<&str>,
+        key: &str,
+        path: &str,
+        val: &T,
+    ) -> Result<()> {
+        let val = serde_json::to_value(val)?;
+        self.update_doc_in(collection, key, |doc| document::set(doc, path, val))
+    }
+
+    pub(crate) fn merge_patch_in(
+        &mut self,
+        collection: Option<&str>,
+        key: &str,
+        patch: &serde_json::Value,
+    ) -> Result<()> {
+        self.update_doc_in(collection, key, |doc| {
+            document::merge_patch(doc, patch);
+            Ok(())
+        })
+    }
+
+    pub(crate) fn apply_patch_in(
+        &mut self,
+        collection: Option<&str>,
+        key: &str,
+        ops: &[PatchOp],
+    ) -> Result<()> {
+        self.update_doc_in(collection, key, |doc| document::apply_patch(doc, ops))
+    }
+
+    /// Get the value of `key` as a JSON document
+    fn get_doc_in(&self, collection: Option<&str>, key: &str) -> Result<serde_json::Value> {
+        self.serializer.check_documents()?;
+        match self.try_get_in(collection, key)? {
+            Some(doc) => Ok(doc),
+            None => Err(DocError::Path(format!("key {} doesn't exist", key))),
+        }
+    }
+
+    /// Modify the value of `key` as a JSON document with `f` and store the result
+    pub(crate) fn update_doc_in<F>(
+        &mut self,
+        collection: Option<&str>,
+        key: &str,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut serde_json::Value) -> Result<()>,
+    {
+        let mut doc = self.get_doc_in(collection, key)?;
+        f(&mut doc)?;
+        self.set_in(collection, key, &doc)
+    }
+
+    pub(crate) fn rem_in(&mut self, collection: Option<&str>, key: &str) -> Result<bool> {
+        self.run_checks(&self.hooks.before_rem, collection, key)?;
+        let removed = match self.take(collection, key)? {
+            // exists key, return old value and dump db now
+            Some(v) => {
+                let was_raw = self.unmark_raw(collection, key);
+                match self.dump_now() {
+                    // dump successfully, return some(v)
+                    Ok(_) => {
+                        self.notify(collection, key, ChangeKind::Removed, Some(v));
+                        true
+                    }
+                    // dump failed, restore key in map
+                    Err(err) => {
+                        self.restore(collection, key, Some(v));
+                        self.remark_raw(key, was_raw);
+                        return Err(err);
+                    }
+                }
+            }
+            None => false,
+        };
+
+        Ok(removed)
+    }
+
+    /// Remove every key of `collection`, or of the top-level keyspace for `None`.
+    pub(crate) fn clear_in(&mut self, collection: Option<&str>) -> Result<()> {
+        if self.keyspace(collection).is_none() {
+            return Ok(());
+        }
+        let original = std::mem::take(self.keyspace_mut(collection));
+        let original_raw_keys = match collection {
+            None => std::mem::take(&mut self.data.raw_keys),
+            Some(_) => HashSet::new(),
+        };
+        let indexes = collection.and_then(|name| self.indexes.get_mut(name));
+        for index in indexes.into_iter().flatten() {
+            index.clear();
+        }
+        #[cfg(feature = "fts")]
+        if let Some(index) = collection.and_then(|name| self.text_indexes.get_mut(name)) {
+            index.clear();
+        }
+        #[cfg(feature = "vector")]
+        let vector_indexes = collection.and_then(|name| self.vector_indexes.get_mut(name));
+        #[cfg(feature = "vector")]
+        for index in vector_indexes.into_iter().flatten() {
+            index.clear();
+        }
+
+        match self.dump_now() {
+            Ok(_) => {
+                for (key, val) in original {
+                    self.notify(collection, &key, ChangeKind::Removed, Some(val));
+                }
+                Ok(())
+            }
+            // dump failed, restore the keys
+            Err(err) => {
+                if let Some(indexes) = collection.and_then(|name| self.indexes.get_mut(name)) {
+                    for index in indexes.iter_mut() {
+                        // the keys were indexed before, so this can't fail
+                        if let Ok(rebuilt) =
+                            Index::build(index.def.clone(), &original, &self.serializer)
+                        {
+                            *index = rebuilt;
+                        }
+                    }
+                }
+                #[cfg(feature = "fts")]
+                if let Some(index) = collection.and_then(|name| self.text_indexes.get_mut(name)) {
+                    if let Ok(rebuilt) =
+                        TextIndex::build(index.def.clone(), &original, &self.serializer)
+                    {
+                        *index = rebuilt;
+                    }
+                }
+                #[cfg(feature = "vector")]
+                let vector_indexes = collection.and_then(|name| self.vector_indexes.get_mut(name));
+                #[cfg(feature = "vector")]
+                for index in vector_indexes.into_iter().flatten() {
+                    if let Ok(rebuilt) =
+                        VectorIndex::build(index.def.clone(), &original, &self.serializer)
+                    {
+                        *index = rebuilt;
+                    }
+                }
+                *self.keyspace_mut(collection) = original;
+                self.data.raw_keys.extend(original_raw_keys);
+                Err(err)
+            }
+        }
+    }
+
+    /// Iterate over the keys of `collection`, none if it doesn't exist
+    pub(crate) fn iter_in(&self, collection: Option<&str>) -> DocDbIterator<'_> {
+        DocDbIterator {
+            map_iter: Box::new(self.keyspace(collection).into_iter().flatten()),
+            serializer: &self.serializer,
+        }
+    }
+
+    pub(crate) fn find_in(
+        &self,
+        collection: Option<&str>,
+        filter: &Filter,
+    ) -> Result<Vec<DocDbIteratorItem<'_>>> {
+        let found = self.find_docs_in(collection, filter)?;
+        Ok(found.into_iter().map(|(item, _)| item).collect())
+    }
+
+    /// Like `find_in`, but also get the documents the values were parsed into
+    pub(crate) fn find_docs_in(
+        &self,
+        collection: Option<&str>,
+        filter: &Filter,
+    ) -> Result<Vec<(DocDbIteratorItem<'_>, serde_json::Value)>> {
+        self.serializer.check_documents()?;
+        let items: Box<dyn Iterator<Item = DocDbIteratorItem>> = match self
+            .keyspace(collection)
+            .zip(self.index_candidates(collection, filter))
+        {
+            Some((map, keys)) => Box::new(keys.into_iter().filter_map(|key| {
+                let (key, value) = map.get_key_value(key)?;
+                Some(DocDbIteratorItem {
+                    key,
+                    value,
+                    serializer: &self.serializer,
+                })
+            })),
+            None => Box::new(self.iter_in(collection)),
+        };
+
+        let mut found = Vec::new();
+        for item in items.filter(|item| !self.is_raw(collection, item.key)) {
+            let doc = item.try_get_doc()?;
+            if filter.matches(&doc) {
+                found.push((item, doc));
+            }
+        }
+        Ok(found)
+    }
+
+    pub(crate) fn aggregate_in(&self, collection: Option<&str>, prefix: &str) -> Aggregate<'_> {
+        let items = self.iter_in(collection);
+        Aggregate::new(
+            &self.serializer,
+            items.filter(|item| item.key.starts_with(prefix) && !self.is_raw(collection, item.key)),
+        )
+    }
+
+    /// Use an index to narrow down the keys that can satisfy `filter`, if there is one
+    fn index_candidates(&self, collection: Option<&str>, filter: &Filter) -> Option<Vec<&String>> {
+        let indexes = self.indexes.get(collection?)?;
+        match filter {
+            Filter::Eq(field, val) => {
+                let index = indexes.iter().find(|index| index.def.field == *field)?;
+                Some(index.get(val).into_iter().flatten().collect())
+            }
+            Filter::And(filters) => filters
+                .iter()
+                .find_map(|filter| self.index_candidates(collection, filter)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn subscribe_in(
+        &mut self,
+        collection: Option<&str>,
+        prefix: &str,
+    ) -> Receiver<Change> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(Subscriber {
+            collection: collection.map(str::to_string),
+            prefix: prefix.to_string(),
+            sender,
+        });
+        receiver
+    }
+
+    /// Send the change of `key` to its subscribers, dropping those whose receiver is gone
+    fn notify(
+        &mut self,
+        collection: Option<&str>,
+        key: &str,
+        kind: ChangeKind,
+        old: Option<Vec<u8>>,
+    ) {
+        if !self.subscribers.iter().any(|s| s.wants(collection, key)) {
+            return;
+        }
+
+        let new = match kind {
+            ChangeKind::Set => self
+                .keyspace(collection)
+                .and_then(|map| map.get(key))
+                .cloned(),
+            ChangeKind::Removed => None,
+        };
+        let serializer = &self.serializer;
+        self.subscribers.retain(|subscriber| {
+            !subscriber.wants(collection, key)
+                || subscriber
+                    .sender
+                    .send(Change {
+                        collection: collection.map(str::to_string),
+                        key: key.to_string(),
+                        kind,
+                        old: old.clone(),
+                        new: new.clone(),
+                        serializer: serializer.clone(),
+                    })
+                    .is_ok()
+        });
+    }
+
+    pub(crate) fn insert_many_in<T: Serialize>(
+        &mut self,
+        collection: Option<&str>,
+        vals: &[T],
+    ) -> Result<Vec<Id>> {
+        let mut ser_vals = Vec::with_capacity(vals.len());
+        for val in vals {
+            ser_vals.push(self.serializer.serialize_data(val)?);
+        }
+
+        let original_last_id = self.data.last_id;
+        let mut ids = Vec::with_capacity(ser_vals.len());
+        for ser_data in ser_vals {
+            let id = self.next_id(collection);
+            let key = id.key();
+            let stored = self
+                .run_before_set(collection, &key, ser_data, false)
+                .and_then(|ser_data| {
+                    self.check_schemas(collection, &key, &ser_data)?;
+                    self.put(collection, &key, ser_data)
+                });
+            if let Err(err) = stored {
+                self.undo_inserts(collection, &ids, original_last_id);
+                return Err(err);
+            }
+            ids.push(id);
+            if let Err(err) = self.run_after_set(collection, &key) {
+                self.undo_inserts(collection, &ids, original_last_id);
+                return Err(err);
+            }
+        }
+
+        match self.dump_now() {
+            Ok(_) => {
+                for id in ids.iter() {
+                    self.notify(collection, &id.key(), ChangeKind::Set, None);
+                }
+                Ok(ids)
+            }
+            Err(err) => {
+                self.undo_inserts(collection, &ids, original_last_id);
+                Err(err)
+            }
+        }
+    }
+
+    /// Remove the values stored under `ids` and give the ids back
+    fn undo_inserts(&mut self, collection: Option<&str>, ids: &[Id], last_id: u64) {
+        for id in ids.iter() {
+            self.restore(collection, &id.key(), None);
+        }
+        self.data.last_id = last_id;
+    }
+
+    /// Hand out the next id whose key isn't already taken in `collection`
+    fn next_id(&mut self, collection: Option<&str>) -> Id {
+        loop {
+            self.data.last_id += 1;
+            let id = Id::new(self.data.last_id);
+            if !self.keyspace_mut(collection).contains_key(&id.key()) {
+                return id;
+            }
+        }
+    }
+
+    pub(crate) fn iter_inserted_in(&self, collection: Option<&str>) -> DocDbIterator<'_> {
+        let mut entries: Vec<(Id, (&String, &Vec<u8>))> = self
+            .keyspace(collection)
+            .into_iter()
+            .flatten()
+            .filter_map(|(k, v)| Some((k.parse::<Id>().ok()?, (k, v))))
+            .collect();
+        entries.sort_by_key(|(id, _)| *id);
+
         DocDbIterator {
-            map_iter: self.map.iter(),
+            map_iter: Box::new(entries.into_iter().map(|(_, entry)| entry)),
             serializer: &self.serializer,
         }
     }
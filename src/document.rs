@@ -0,0 +1,218 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::{DocError, Result};
+use crate::query::compare;
+
+/// An operation of a JSON Patch (RFC 6902), applied by [DocDb::apply_patch](crate::DocDb::apply_patch).
+///
+/// Deserializes from the JSON form of the RFC, e.g.
+/// `{"op": "replace", "path": "/server/port", "value": 8080}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    /// Add a member to an object, insert an element into an array, or replace the root
+    Add { path: String, value: Value },
+    /// Remove the value at `path`, which must exist
+    Remove { path: String },
+    /// Replace the value at `path`, which must exist
+    Replace { path: String, value: Value },
+    /// Remove the value at `from` and add it at `path`
+    Move { from: String, path: String },
+    /// Add a copy of the value at `from` at `path`
+    Copy { from: String, path: String },
+    /// Check that the value at `path` equals `value`
+    Test { path: String, value: Value },
+}
+
+/// Split a JSON Pointer (RFC 6901) into its unescaped reference tokens
+fn tokens(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    if !pointer.starts_with('/') {
+        return Err(path_err(pointer, "a JSON pointer must start with '/'"));
+    }
+
+    Ok(pointer[1..]
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn path_err(pointer: &str, msg: &str) -> DocError {
+    DocError::Path(format!("{}: {}", pointer, msg))
+}
+
+/// Get the array index a reference token points to, `-` being one past the end
+fn array_index(pointer: &str, token: &str, len: usize) -> Result<usize> {
+    if token == "-" {
+        return Ok(len);
+    }
+    match token.parse::<usize>() {
+        Ok(idx) if idx <= len && (token == "0" || !token.starts_with('0')) => Ok(idx),
+        _ => Err(path_err(pointer, "array index out of bounds")),
+    }
+}
+
+/// Get the value `pointer` refers to in `doc`
+pub(crate) fn get<'a>(doc: &'a Value, pointer: &str) -> Result<&'a Value> {
+    doc.pointer(pointer)
+        .ok_or_else(|| path_err(pointer, "no such path"))
+}
+
+fn get_mut<'a>(doc: &'a mut Value, pointer: &str) -> Result<&'a mut Value> {
+    doc.pointer_mut(pointer)
+        .ok_or_else(|| path_err(pointer, "no such path"))
+}
+
+/// Get the parent of the value `pointer` refers to, and the last reference token.
+/// The parent must exist; the value itself may not.
+fn parent<'a>(doc: &'a mut Value, pointer: &str) -> Result<(&'a mut Value, String)> {
+    let mut tokens = tokens(pointer)?;
+    let last = match tokens.pop() {
+        Some(last) => last,
+        None => return Err(path_err(pointer, "the document root has no parent")),
+    };
+
+    let mut parent = doc;
+    for token in tokens.iter() {
+        parent = match parent {
+            Value::Object(obj) => obj.get_mut(token),
+            Value::Array(arr) => token.parse::<usize>().ok().and_then(|i| arr.get_mut(i)),
+            _ => None,
+        }
+        .ok_or_else(|| path_err(pointer, "no such path"))?;
+    }
+    Ok((parent, last))
+}
+
+/// Set the value `pointer` refers to, replacing an existing value.
+///
+/// A missing object member is created and an array index one past the end
+/// (or `-`) appends to the array, but the parent must exist.
+pub(crate) fn set(doc: &mut Value, pointer: &str, val: Value) -> Result<()> {
+    insert(doc, pointer, val, false)
+}
+
+/// Add the value `pointer` refers to, the way the JSON Patch `add` operation does:
+/// unlike [set], an array index inserts before the element at that index.
+fn add(doc: &mut Value, pointer: &str, val: Value) -> Result<()> {
+    insert(doc, pointer, val, true)
+}
+
+fn insert(doc: &mut Value, pointer: &str, val: Value, shift: bool) -> Result<()> {
+    if pointer.is_empty() {
+        *doc = val;
+        return Ok(());
+    }
+
+    let (parent, last) = parent(doc, pointer)?;
+    match parent {
+        Value::Object(obj) => {
+            obj.insert(last, val);
+        }
+        Value::Array(arr) => match array_index(pointer, &last, arr.len())? {
+            idx if shift || idx == arr.len() => arr.insert(idx, val),
+            idx => arr[idx] = val,
+        },
+        _ => {
+            return Err(path_err(
+                pointer,
+                "parent is neither an object nor an array",
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Remove the value `pointer` refers to and return it
+fn remove(doc: &mut Value, pointer: &str) -> Result<Value> {
+    let (parent, last) = parent(doc, pointer)?;
+    let removed = match parent {
+        Value::Object(obj) => obj.remove(&last),
+        Value::Array(arr) => match array_index(pointer, &last, arr.len()) {
+            Ok(idx) if idx < arr.len() => Some(arr.remove(idx)),
+            _ => None,
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| path_err(pointer, "no such path"))
+}
+
+/// Apply `ops` to `doc` in order, stopping at the first one that fails
+pub(crate) fn apply_patch(doc: &mut Value, ops: &[PatchOp]) -> Result<()> {
+    for op in ops {
+        match op {
+            PatchOp::Add { path, value } => add(doc, path, value.clone())?,
+            PatchOp::Remove { path } => {
+                remove(doc, path)?;
+            }
+            PatchOp::Replace { path, value } => *get_mut(doc, path)? = value.clone(),
+            PatchOp::Move { from, path } => {
+                if path.starts_with(&format!("{}/", from)) {
+                    return Err(path_err(from, "cannot be moved into one of its children"));
+                }
+                let val = remove(doc, from)?;
+                add(doc, path, val)?;
+            }
+            PatchOp::Copy { from, path } => {
+                let val = get(doc, from)?.clone();
+                add(doc, path, val)?;
+            }
+            PatchOp::Test { path, value } => {
+                if !values_eq(get(doc, path)?, value) {
+                    return Err(DocError::Patch(format!(
+                        "test failed: {} is not {}",
+                        path, value
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check whether two values are equal as JSON values, so that numbers are
+/// compared by value: `1` equals `1.0`
+fn values_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(_), Value::Number(_)) => compare(a, b) == Some(Ordering::Equal),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_eq(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(name, a)| b.get(name).is_some_and(|b| values_eq(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Apply a JSON Merge Patch (RFC 7396) to `doc`: the members of `patch` replace
+/// those of `doc` recursively, and `null` members remove them
+pub(crate) fn merge_patch(doc: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => {
+            *doc = patch.clone();
+            return;
+        }
+    };
+
+    if !doc.is_object() {
+        *doc = Value::Object(Map::new());
+    }
+    if let Value::Object(obj) = doc {
+        for (name, val) in patch {
+            if val.is_null() {
+                obj.remove(name);
+            } else {
+                merge_patch(obj.entry(name.as_str()).or_insert(Value::Null), val);
+            }
+        }
+    }
+}
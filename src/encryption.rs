@@ -0,0 +1,69 @@
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+use crate::error::{DocError, Result};
+
+/// Magic bytes at the start of an encrypted db file, followed by the version of the
+/// layout, the nonce and the ciphertext. They are authenticated along with the content.
+const MAGIC: &[u8; 4] = b"DDBX";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 1;
+const NONCE_LEN: usize = 24;
+
+/// The cipher of an encrypted DB: XChaCha20-Poly1305, whose random 192-bit nonces
+/// can't collide however many times the file is dumped
+#[derive(Clone)]
+pub(crate) struct Cipher(XChaCha20Poly1305);
+
+impl Cipher {
+    pub(crate) fn new(key: &[u8; 32]) -> Self {
+        Self(XChaCha20Poly1305::new(key.into()))
+    }
+
+    /// Encrypt the content of a db file behind a header
+    pub(crate) fn encrypt(&self, content: &[u8]) -> Result<Vec<u8>> {
+        let header = [&MAGIC[..], &[VERSION]].concat();
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: content,
+            aad: &header,
+        };
+        match self.0.encrypt(&nonce, payload) {
+            Ok(ciphertext) => Ok([header, nonce.to_vec(), ciphertext].concat()),
+            Err(err) => Err(DocError::Serialization(err.to_string())),
+        }
+    }
+
+    /// Decrypt the content of a db file, which must have been encrypted with the same key
+    pub(crate) fn decrypt(&self, content: &[u8]) -> Result<Vec<u8>> {
+        if !is_encrypted(content) {
+            return Err(DocError::Decryption(
+                "the db file isn't encrypted".to_string(),
+            ));
+        }
+        if content[MAGIC.len()] != VERSION || content.len() < HEADER_LEN + NONCE_LEN {
+            return Err(DocError::Decryption(
+                "the db file has an unknown or truncated header".to_string(),
+            ));
+        }
+
+        let (header, rest) = content.split_at(HEADER_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: header,
+        };
+        match self.0.decrypt(XNonce::from_slice(nonce), payload) {
+            Ok(plaintext) => Ok(plaintext),
+            // the error is opaque by design
+            Err(_) => Err(DocError::Decryption(
+                "wrong key, or the db file was tampered with".to_string(),
+            )),
+        }
+    }
+}
+
+/// Check whether the content of a db file is encrypted
+pub(crate) fn is_encrypted(content: &[u8]) -> bool {
+    content.len() > MAGIC.len() && content.starts_with(MAGIC)
+}
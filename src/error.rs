@@ -7,6 +7,8 @@ use std::str::Utf8Error;
 pub enum ErrorType {
     IO,
     Serialization,
+    Query,
+    Validation,
 }
 
 #[derive(Debug)]
@@ -14,12 +16,42 @@ pub enum DocError {
     IO(io::Error),
     Serialization(String),
     Deserialization(String),
+    /// An invalid query or filter
+    Query(String),
+    /// An invalid path into a document, or one that doesn't exist
+    Path(String),
+    /// A JSON Patch whose `test` operation failed
+    Patch(String),
+    /// A write rejected because it would store a value already held by another
+    /// document in a unique index
+    UniqueViolation(String),
+    /// A write rejected by a hook, see [DocDb::on_before_set](crate::DocDb::on_before_set)
+    Rejected(String),
+    /// A document that doesn't conform to the JSON Schema of its key, see
+    /// [DocDb::set_schema](crate::DocDb::set_schema). `path` is a JSON Pointer to the
+    /// failing value, empty for the whole document.
+    SchemaViolation {
+        path: String,
+        message: String,
+    },
+    /// An encrypted db file that can't be decrypted with the given key, because the
+    /// key is wrong or the file was tampered with, see
+    /// [DocDb::load_encrypted](crate::DocDb::load_encrypted)
+    Decryption(String),
+    /// An operation the serialization method can't support, such as reading values as
+    /// JSON documents with bincode, which isn't self-describing
+    Unsupported(String),
 }
 
 impl DocError {
     pub fn get_type(&self) -> ErrorType {
         match self {
             DocError::IO(_) => ErrorType::IO,
+            DocError::Query(_) | DocError::Path(_) => ErrorType::Query,
+            DocError::UniqueViolation(_)
+            | DocError::Patch(_)
+            | DocError::Rejected(_)
+            | DocError::SchemaViolation { .. } => ErrorType::Validation,
             _ => ErrorType::Serialization,
         }
     }
@@ -31,6 +63,18 @@ impl Display for DocError {
             DocError::IO(err) => fmt::Display::fmt(err, f),
             DocError::Serialization(err) => f.write_str(&format!("Serialization err: {}", err)),
             DocError::Deserialization(err) => f.write_str(&format!("Deserialization err: {}", err)),
+            DocError::Decryption(err) => f.write_str(&format!("Decryption err: {}", err)),
+            DocError::Unsupported(err) => f.write_str(&format!("Unsupported: {}", err)),
+            DocError::Query(err) => f.write_str(&format!("Query err: {}", err)),
+            DocError::Path(err) => f.write_str(&format!("Path err: {}", err)),
+            DocError::Patch(err) => f.write_str(&format!("Patch err: {}", err)),
+            DocError::UniqueViolation(err) => f.write_str(&format!("Unique violation: {}", err)),
+            DocError::Rejected(err) => f.write_str(&format!("Rejected: {}", err)),
+            DocError::SchemaViolation { path, message } => f.write_str(&format!(
+                "Schema violation at {}: {}",
+                if path.is_empty() { "the root" } else { path },
+                message
+            )),
         }
     }
 }
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::index::TextIndexDef;
+use crate::query::lookup;
+use crate::serialization::{DbMap, Serializer};
+
+/// A full-text index of string fields of the documents of a collection
+pub(crate) struct TextIndex {
+    pub(crate) def: TextIndexDef,
+    /// term -> key of the documents holding it -> number of occurrences
+    postings: HashMap<String, HashMap<String, u32>>,
+}
+
+impl TextIndex {
+    /// Index all the documents of `map`
+    pub(crate) fn build(def: TextIndexDef, map: &DbMap, serializer: &Serializer) -> Result<Self> {
+        let mut index = TextIndex {
+            def,
+            postings: HashMap::new(),
+        };
+
+        for (key, val) in map.iter() {
+            let doc = serializer.try_deserialize_doc(val)?;
+            index.insert(key, &doc);
+        }
+        Ok(index)
+    }
+
+    /// Count the occurrences of each term of the indexed fields of `doc`
+    fn terms(&self, doc: &Value) -> HashMap<String, u32> {
+        let mut terms = HashMap::new();
+        for field in self.def.fields.iter() {
+            let texts = match lookup(doc, field) {
+                Some(Value::String(text)) => vec![text.as_str()],
+                Some(Value::Array(arr)) => arr.iter().filter_map(Value::as_str).collect(),
+                _ => vec![],
+            };
+            for term in texts.into_iter().flat_map(tokenize) {
+                *terms.entry(term).or_default() += 1;
+            }
+        }
+        terms
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, doc: &Value) {
+        for (term, count) in self.terms(doc) {
+            self.postings
+                .entry(term)
+                .or_default()
+                .insert(key.to_string(), count);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: &str, doc: &Value) {
+        for term in self.terms(doc).into_keys() {
+            if let Some(keys) = self.postings.get_mut(&term) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.postings.clear();
+    }
+
+    /// Get the keys of the documents holding any term of `query`, best match first.
+    ///
+    /// Documents are ranked by TF-IDF: a term counts more the more often it occurs
+    /// in the document and the fewer of the `total` documents hold it.
+    pub(crate) fn search(&self, query: &str, total: usize) -> Vec<String> {
+        let mut scores: HashMap<&str, f64> = HashMap::new();
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        for term in terms.iter() {
+            let keys = match self.postings.get(term) {
+                Some(keys) => keys,
+                None => continue,
+            };
+            let idf = (1.0 + total as f64 / keys.len() as f64).ln();
+            for (key, count) in keys.iter() {
+                *scores.entry(key).or_default() += *count as f64 * idf;
+            }
+        }
+
+        let mut ranked: Vec<(&str, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|(a_key, a), (b_key, b)| b.total_cmp(a).then_with(|| a_key.cmp(b_key)));
+        ranked.into_iter().map(|(key, _)| key.to_string()).collect()
+    }
+}
+
+/// Split `text` into lowercase terms at every character that isn't alphanumeric
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
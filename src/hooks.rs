@@ -0,0 +1,49 @@
+use serde_json::Value;
+
+use crate::error::Result;
+
+/// A hook run before a value is set, which may modify the value or reject the write
+pub(crate) type BeforeSet = dyn Fn(&str, &mut Value) -> Result<()> + Send + Sync;
+/// A hook run after a value is set or before it is removed, which may reject the write
+pub(crate) type Check = dyn Fn(&str, &Value) -> Result<()> + Send + Sync;
+
+/// A hook that applies to the keys of a keyspace that start with a prefix
+pub(crate) struct Hook<F: ?Sized> {
+    collection: Option<String>,
+    prefix: String,
+    f: Box<F>,
+}
+
+impl<F: ?Sized> Hook<F> {
+    pub(crate) fn new(collection: Option<&str>, prefix: &str, f: Box<F>) -> Self {
+        Self {
+            collection: collection.map(str::to_string),
+            prefix: prefix.to_string(),
+            f,
+        }
+    }
+
+    fn applies(&self, collection: Option<&str>, key: &str) -> bool {
+        self.collection.as_deref() == collection && key.starts_with(&self.prefix)
+    }
+}
+
+/// The hooks registered on a db, run in the order they were registered
+#[derive(Default)]
+pub(crate) struct Hooks {
+    pub(crate) before_set: Vec<Hook<BeforeSet>>,
+    pub(crate) after_set: Vec<Hook<Check>>,
+    pub(crate) before_rem: Vec<Hook<Check>>,
+}
+
+/// Get the hooks of `hooks` that apply to `key`
+pub(crate) fn matching<'a, F: ?Sized>(
+    hooks: &'a [Hook<F>],
+    collection: Option<&'a str>,
+    key: &'a str,
+) -> impl Iterator<Item = &'a F> + 'a {
+    hooks
+        .iter()
+        .filter(move |hook| hook.applies(collection, key))
+        .map(|hook| hook.f.as_ref())
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::DocError;
+
+/// Number of digits of a key produced by [Id::key], enough for any `u64`
+const KEY_LEN: usize = 20;
+
+/// Identifier of a document stored with [DocDb::insert](crate::DocDb::insert).
+///
+/// Ids are handed out in increasing order and never reused. The key a document
+/// is stored under is the zero-padded id (see [Id::key]), so keys sort in
+/// insertion order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id(u64);
+
+impl Id {
+    pub(crate) fn new(id: u64) -> Self {
+        Self(id)
+    }
+
+    /// Get the numeric value of the id.
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    /// Get the key the document with this id is stored under.
+    pub fn key(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:0width$}", self.0, width = KEY_LEN)
+    }
+}
+
+impl FromStr for Id {
+    type Err = DocError;
+
+    /// Parse a key produced by [Id::key]. Any other key, such as `"7"`, is an error.
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        let not_an_id = || DocError::Deserialization(format!("{:?} isn't the key of an id", key));
+        if key.len() != KEY_LEN || !key.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(not_an_id());
+        }
+        key.parse().map(Id).map_err(|_| not_an_id())
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{DocError, Result};
+use crate::query::lookup;
+use crate::serialization::{DbMap, Serializer};
+
+/// Definition of a secondary index, stored in the db file so that the index
+/// can be rebuilt when the db is loaded
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct IndexDef {
+    pub(crate) collection: String,
+    pub(crate) field: String,
+    pub(crate) unique: bool,
+}
+
+/// Definition of a full-text index over string fields of a collection, stored in
+/// the db file like [IndexDef]. The index itself needs the `fts` feature.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct TextIndexDef {
+    pub(crate) collection: String,
+    pub(crate) fields: Vec<String>,
+}
+
+/// Definition of an approximate nearest-neighbor index over a vector field of a
+/// collection, stored in the db file like [IndexDef]. The index itself needs the
+/// `vector` feature.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct VectorIndexDef {
+    pub(crate) collection: String,
+    pub(crate) field: String,
+    /// number of lists the vectors are partitioned into
+    pub(crate) lists: usize,
+    /// number of lists searched by a query
+    pub(crate) probes: usize,
+}
+
+/// An index of one field of the documents of a collection.
+///
+/// A document whose field is an array is indexed under the whole array and
+/// under each of its elements, the same way [Filter::Eq](crate::Filter::Eq) matches it.
+pub(crate) struct Index {
+    pub(crate) def: IndexDef,
+    /// canonical form of an indexed value -> keys of the documents holding it
+    entries: HashMap<String, BTreeSet<String>>,
+}
+
+impl Index {
+    /// Index all the documents of `map`
+    pub(crate) fn build(def: IndexDef, map: &DbMap, serializer: &Serializer) -> Result<Self> {
+        let mut index = Index {
+            def,
+            entries: HashMap::new(),
+        };
+
+        for (key, val) in map.iter() {
+            let doc = serializer.try_deserialize_doc(val)?;
+            index.check(key, &doc)?;
+            index.insert(key, &doc);
+        }
+        Ok(index)
+    }
+
+    fn entry_keys(&self, doc: &Value) -> Vec<String> {
+        match lookup(doc, &self.def.field) {
+            Some(Value::Array(arr)) => {
+                let mut keys: Vec<String> = arr.iter().map(canonical).collect();
+                keys.push(canonical(&Value::Array(arr.clone())));
+                keys
+            }
+            Some(val) => vec![canonical(val)],
+            None => vec![],
+        }
+    }
+
+    /// Check that storing `doc` under `key` doesn't break the uniqueness of the index
+    pub(crate) fn check(&self, key: &str, doc: &Value) -> Result<()> {
+        if !self.def.unique {
+            return Ok(());
+        }
+
+        for entry in self.entry_keys(doc) {
+            let other = self
+                .entries
+                .get(&entry)
+                .and_then(|keys| keys.iter().find(|k| *k != key));
+            if let Some(other) = other {
+                return Err(DocError::UniqueViolation(format!(
+                    "{}.{} value {} is already used by key {}",
+                    self.def.collection, self.def.field, entry, other
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, doc: &Value) {
+        for entry in self.entry_keys(doc) {
+            self.entries
+                .entry(entry)
+                .or_default()
+                .insert(key.to_string());
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: &str, doc: &Value) {
+        for entry in self.entry_keys(doc) {
+            if let Some(keys) = self.entries.get_mut(&entry) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.entries.remove(&entry);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Get the keys of the documents whose field equals `val`
+    pub(crate) fn get(&self, val: &Value) -> Option<&BTreeSet<String>> {
+        self.entries.get(&canonical(val))
+    }
+}
+
+/// Render `val` so that values that compare equal get the same index entry
+pub(crate) fn canonical(val: &Value) -> String {
+    match val {
+        Value::Number(n) if n.is_f64() => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => (f as i64).to_string(),
+            _ => n.to_string(),
+        },
+        _ => val.to_string(),
+    }
+}
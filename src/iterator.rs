@@ -1,12 +1,11 @@
-use std::collections::hash_map;
-
 use serde::de::DeserializeOwned;
+use serde_json::Value;
 
+use crate::error::Result;
 use crate::serialization::Serializer;
 
 pub struct DocDbIterator<'a> {
-    ///
-    pub(crate) map_iter: hash_map::Iter<'a, String, Vec<u8>>,
+    pub(crate) map_iter: Box<dyn Iterator<Item = (&'a String, &'a Vec<u8>)> + 'a>,
     pub(crate) serializer: &'a Serializer,
 }
 
@@ -25,11 +24,11 @@ impl<'a> Iterator for DocDbIterator<'a> {
     }
 }
 
+#[derive(Clone)]
 pub struct DocDbIteratorItem<'a> {
-    ///
-    key: &'a str,
-    value: &'a Vec<u8>,
-    serializer: &'a Serializer,
+    pub(crate) key: &'a str,
+    pub(crate) value: &'a Vec<u8>,
+    pub(crate) serializer: &'a Serializer,
 }
 
 impl<'a> DocDbIteratorItem<'a> {
@@ -40,4 +39,17 @@ impl<'a> DocDbIteratorItem<'a> {
     pub fn get_value<T: DeserializeOwned>(&self) -> Option<T> {
         self.serializer.deserialize_data(self.value)
     }
+
+    /// Get the value as it is stored, see [DocDb::get_raw](crate::DocDb::get_raw)
+    pub fn get_raw_value(&self) -> &[u8] {
+        self.value
+    }
+
+    pub(crate) fn try_get_value<T: DeserializeOwned>(&self) -> Result<T> {
+        self.serializer.try_deserialize_data(self.value)
+    }
+
+    pub(crate) fn try_get_doc(&self) -> Result<Value> {
+        self.serializer.try_deserialize_doc(self.value)
+    }
 }
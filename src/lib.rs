@@ -1,9 +1,36 @@
+mod aggregate;
+mod collection;
+mod compression;
 mod db;
+mod document;
+#[cfg(feature = "encryption")]
+mod encryption;
+#[cfg(feature = "fts")]
+mod fts;
+mod hooks;
+mod id;
+mod index;
 mod iterator;
+mod query;
+mod schema;
 mod serialization;
+mod table;
+#[cfg(feature = "vector")]
+mod vector;
+mod watch;
 
 pub mod error;
 
-pub use db::{DocDb, DumpPolicy};
+pub use aggregate::Aggregate;
+pub use collection::Collection;
+pub use compression::Compression;
+pub use db::{DocDb, DumpPolicy, Migration};
+pub use document::PatchOp;
+pub use id::Id;
 pub use iterator::{DocDbIterator, DocDbIteratorItem};
-pub use serialization::SerializationMethod;
+pub use query::{Filter, Order, Query};
+pub use serialization::{DocSerializer, SerializationMethod, SerializerOptions};
+pub use table::{Table, TableIterator};
+#[cfg(feature = "vector")]
+pub use vector::Metric;
+pub use watch::{Change, ChangeKind};
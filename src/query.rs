@@ -0,0 +1,529 @@
+use std::cmp::Ordering;
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::db::DocDb;
+use crate::error::{DocError, Result};
+
+/// A predicate over the fields of a document, used by [DocDb::find](crate::DocDb::find).
+///
+/// Fields are addressed by a dotted path, e.g. `"address.city"`; a numeric
+/// segment indexes into an array. Filters are evaluated over the stored values
+/// regardless of the [SerializationMethod](crate::SerializationMethod), as long as
+/// it is self-describing (i.e. not bincode).
+///
+/// A filter can be built with the constructors below, with the [filter!](crate::filter)
+/// macro or from a MongoDB-style JSON object with [Filter::from_json].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// The field equals the value. If the field is an array, any element may match.
+    Eq(String, Value),
+    /// The field is missing or doesn't equal the value
+    Ne(String, Value),
+    Gt(String, Value),
+    Gte(String, Value),
+    Lt(String, Value),
+    Lte(String, Value),
+    /// The field equals one of the values
+    In(String, Vec<Value>),
+    /// The field exists (`true`) or doesn't exist (`false`)
+    Exists(String, bool),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn eq<V: Into<Value>>(field: &str, value: V) -> Self {
+        Filter::Eq(field.to_string(), value.into())
+    }
+
+    pub fn ne<V: Into<Value>>(field: &str, value: V) -> Self {
+        Filter::Ne(field.to_string(), value.into())
+    }
+
+    pub fn gt<V: Into<Value>>(field: &str, value: V) -> Self {
+        Filter::Gt(field.to_string(), value.into())
+    }
+
+    pub fn gte<V: Into<Value>>(field: &str, value: V) -> Self {
+        Filter::Gte(field.to_string(), value.into())
+    }
+
+    pub fn lt<V: Into<Value>>(field: &str, value: V) -> Self {
+        Filter::Lt(field.to_string(), value.into())
+    }
+
+    pub fn lte<V: Into<Value>>(field: &str, value: V) -> Self {
+        Filter::Lte(field.to_string(), value.into())
+    }
+
+    pub fn is_in<V: Into<Value>>(field: &str, values: Vec<V>) -> Self {
+        Filter::In(
+            field.to_string(),
+            values.into_iter().map(Into::into).collect(),
+        )
+    }
+
+    pub fn exists(field: &str) -> Self {
+        Filter::Exists(field.to_string(), true)
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Filter::And(mut filters) => {
+                filters.push(other);
+                Filter::And(filters)
+            }
+            filter => Filter::And(vec![filter, other]),
+        }
+    }
+
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Filter::Or(mut filters) => {
+                filters.push(other);
+                Filter::Or(filters)
+            }
+            filter => Filter::Or(vec![filter, other]),
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Filter::Not(Box::new(self))
+    }
+
+    /// Build a filter from a MongoDB-style JSON object, e.g.
+    /// `{"age": {"$gt": 30}, "city": "Berlin"}`.
+    ///
+    /// Supported operators are `$eq`, `$ne`, `$gt`, `$gte`, `$lt`, `$lte`, `$in`,
+    /// `$nin`, `$exists` and `$not` on fields, and `$and`, `$or`, `$nor` at the top level.
+    pub fn from_json(filter: &Value) -> Result<Self> {
+        let obj = match filter {
+            Value::Object(obj) => obj,
+            _ => return Err(query_err("filter must be a JSON object")),
+        };
+
+        let mut filters = Vec::with_capacity(obj.len());
+        for (key, val) in obj.iter() {
+            let filter = match key.as_str() {
+                "$and" => Filter::And(Self::from_json_list(key, val)?),
+                "$or" => Filter::Or(Self::from_json_list(key, val)?),
+                "$nor" => Filter::Or(Self::from_json_list(key, val)?).not(),
+                op if op.starts_with('$') => {
+                    return Err(query_err(&format!("unknown operator {}", op)))
+                }
+                field => Self::from_json_field(field, val)?,
+            };
+            filters.push(filter);
+        }
+
+        Ok(Self::all(filters))
+    }
+
+    fn from_json_list(op: &str, val: &Value) -> Result<Vec<Self>> {
+        match val {
+            Value::Array(filters) => filters.iter().map(Self::from_json).collect(),
+            _ => Err(query_err(&format!("{} expects an array of filters", op))),
+        }
+    }
+
+    fn from_json_field(field: &str, val: &Value) -> Result<Self> {
+        let ops = match val {
+            Value::Object(ops) if ops.keys().any(|k| k.starts_with('$')) => ops,
+            // a plain value is an equality match
+            _ => return Ok(Filter::eq(field, val.clone())),
+        };
+
+        let mut filters = Vec::with_capacity(ops.len());
+        for (op, arg) in ops.iter() {
+            let filter = match op.as_str() {
+                "$eq" => Filter::eq(field, arg.clone()),
+                "$ne" => Filter::ne(field, arg.clone()),
+                "$gt" => Filter::gt(field, arg.clone()),
+                "$gte" => Filter::gte(field, arg.clone()),
+                "$lt" => Filter::lt(field, arg.clone()),
+                "$lte" => Filter::lte(field, arg.clone()),
+                "$in" => Filter::In(field.to_string(), Self::json_values(op, arg)?),
+                "$nin" => Filter::In(field.to_string(), Self::json_values(op, arg)?).not(),
+                "$exists" => match arg {
+                    Value::Bool(exists) => Filter::Exists(field.to_string(), *exists),
+                    _ => return Err(query_err("$exists expects a boolean")),
+                },
+                "$not" => Self::from_json_field(field, arg)?.not(),
+                _ => return Err(query_err(&format!("unknown operator {}", op))),
+            };
+            filters.push(filter);
+        }
+
+        Ok(Self::all(filters))
+    }
+
+    /// Combine `filters` into a single `And`, flattening nested ones
+    fn all(filters: Vec<Filter>) -> Self {
+        let mut all = Vec::with_capacity(filters.len());
+        for filter in filters {
+            match filter {
+                Filter::And(inner) => all.extend(inner),
+                filter => all.push(filter),
+            }
+        }
+
+        match all.len() {
+            1 => all.remove(0),
+            _ => Filter::And(all),
+        }
+    }
+
+    fn json_values(op: &str, val: &Value) -> Result<Vec<Value>> {
+        match val {
+            Value::Array(values) => Ok(values.clone()),
+            _ => Err(query_err(&format!("{} expects an array", op))),
+        }
+    }
+
+    /// Check whether `doc` satisfies the filter
+    pub fn matches(&self, doc: &Value) -> bool {
+        match self {
+            Filter::Eq(field, val) => lookup(doc, field).is_some_and(|v| contains(v, val)),
+            Filter::Ne(field, val) => !lookup(doc, field).is_some_and(|v| contains(v, val)),
+            Filter::Gt(field, val) => compare_field(doc, field, val, |o| o.is_gt()),
+            Filter::Gte(field, val) => compare_field(doc, field, val, |o| o.is_ge()),
+            Filter::Lt(field, val) => compare_field(doc, field, val, |o| o.is_lt()),
+            Filter::Lte(field, val) => compare_field(doc, field, val, |o| o.is_le()),
+            Filter::In(field, vals) => {
+                lookup(doc, field).is_some_and(|v| vals.iter().any(|val| contains(v, val)))
+            }
+            Filter::Exists(field, exists) => lookup(doc, field).is_some() == *exists,
+            Filter::And(filters) => filters.iter().all(|f| f.matches(doc)),
+            Filter::Or(filters) => filters.iter().any(|f| f.matches(doc)),
+            Filter::Not(filter) => !filter.matches(doc),
+        }
+    }
+}
+
+/// Direction of a [Query::sort_by]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+/// A query over the documents of a [DocDb] or of one of its collections, returned by
+/// [DocDb::query] and [Collection::query](crate::Collection::query).
+///
+/// Each stored value is read once as a JSON document, which is filtered, sorted,
+/// paginated and projected before it is deserialized into the caller's type, so only
+/// the selected fields of the returned documents have to fit that type. Like [Filter],
+/// this doesn't work with bincode: fetching fails with a [DocError::Unsupported].
+///
+/// ```
+/// use docdb::{filter, DocDb, DumpPolicy, Order, SerializationMethod};
+/// # let mut db = DocDb::new("query_doc.db", DumpPolicy::NeverDump, SerializationMethod::Json);
+/// # db.set("1", &serde_json::json!({"name": "ann", "score": 12})).unwrap();
+///
+/// let top: Vec<(String, serde_json::Value)> = db
+///     .query()
+///     .filter(filter!(score > 10))
+///     .sort_by("score", Order::Desc)
+///     .limit(20)
+///     .project(&["name", "score"])
+///     .fetch()
+///     .unwrap();
+/// ```
+pub struct Query<'a> {
+    db: &'a DocDb,
+    collection: Option<&'a str>,
+    filter: Option<Filter>,
+    sort: Vec<(String, Order)>,
+    skip: usize,
+    limit: Option<usize>,
+    fields: Option<Vec<String>>,
+}
+
+impl<'a> Query<'a> {
+    pub(crate) fn new(db: &'a DocDb, collection: Option<&'a str>) -> Self {
+        Self {
+            db,
+            collection,
+            filter: None,
+            sort: Vec::new(),
+            skip: 0,
+            limit: None,
+            fields: None,
+        }
+    }
+
+    /// Only return the documents that satisfy `filter`.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(match self.filter {
+            Some(current) => current.and(filter),
+            None => filter,
+        });
+        self
+    }
+
+    /// Sort the documents by `field`. Further calls sort documents with equal
+    /// values of the previous fields.
+    ///
+    /// Documents that don't have the field sort like `null`, before every other
+    /// value in ascending order. Documents with equal values are sorted by key.
+    pub fn sort_by(mut self, field: &str, order: Order) -> Self {
+        self.sort.push((field.to_string(), order));
+        self
+    }
+
+    /// Skip the first `n` documents.
+    pub fn skip(mut self, n: usize) -> Self {
+        self.skip = n;
+        self
+    }
+
+    /// Return at most `n` documents.
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Only keep `fields` of the documents, as dotted paths. Fields a document
+    /// doesn't have are left out of it.
+    pub fn project(mut self, fields: &[&str]) -> Self {
+        self.fields = Some(fields.iter().map(|field| field.to_string()).collect());
+        self
+    }
+
+    /// Run the query and get the keys and the deserialized documents.
+    pub fn fetch<T: DeserializeOwned>(&self) -> Result<Vec<(String, T)>> {
+        let filter = self.filter.clone().unwrap_or(Filter::And(vec![]));
+        let mut docs: Vec<(&str, Value)> = self
+            .db
+            .find_docs_in(self.collection, &filter)?
+            .into_iter()
+            .map(|(item, doc)| (item.key, doc))
+            .collect();
+
+        docs.sort_by(|(a_key, a), (b_key, b)| {
+            self.sort
+                .iter()
+                .map(|(field, order)| {
+                    let null = Value::Null;
+                    let ord = sort_order(
+                        lookup(a, field).unwrap_or(&null),
+                        lookup(b, field).unwrap_or(&null),
+                    );
+                    match order {
+                        Order::Asc => ord,
+                        Order::Desc => ord.reverse(),
+                    }
+                })
+                .find(|ord| ord.is_ne())
+                .unwrap_or_else(|| a_key.cmp(b_key))
+        });
+
+        docs.into_iter()
+            .skip(self.skip)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|(key, doc)| {
+                let doc = match &self.fields {
+                    Some(fields) => project(&doc, fields),
+                    None => doc,
+                };
+                let doc = T::deserialize(doc)
+                    .map_err(|err| DocError::Deserialization(err.to_string()))?;
+                Ok((key.to_string(), doc))
+            })
+            .collect()
+    }
+}
+
+/// Build a document with only `fields` of `doc`
+fn project(doc: &Value, fields: &[String]) -> Value {
+    let mut projected = Value::Object(Map::new());
+    for field in fields {
+        let val = match lookup(doc, field) {
+            Some(val) => val,
+            None => continue,
+        };
+
+        let mut target = &mut projected;
+        for segment in field.split('.') {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            target = match target {
+                Value::Object(obj) => obj.entry(segment).or_insert(Value::Null),
+                _ => unreachable!("target was just made an object"),
+            };
+        }
+        *target = val.clone();
+    }
+    projected
+}
+
+fn query_err(msg: &str) -> DocError {
+    DocError::Query(msg.to_string())
+}
+
+/// Get the value at the dotted `path` of `doc`
+pub(crate) fn lookup<'a>(doc: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(doc, |val, segment| match val {
+        Value::Object(obj) => obj.get(segment),
+        Value::Array(arr) => arr.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Compare two values of the same kind; values of different kinds are unordered
+pub(crate) fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        },
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+/// Order any two values: values of different kinds are ordered by kind, null
+/// first, then booleans, numbers, strings, arrays and objects
+pub(crate) fn sort_order(a: &Value, b: &Value) -> Ordering {
+    fn kind_rank(val: &Value) -> u8 {
+        match val {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
+        }
+    }
+
+    kind_rank(a)
+        .cmp(&kind_rank(b))
+        .then_with(|| compare(a, b).unwrap_or_else(|| a.to_string().cmp(&b.to_string())))
+}
+
+fn equals(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(_), Value::Number(_)) => compare(a, b) == Some(Ordering::Equal),
+        _ => a == b,
+    }
+}
+
+/// `field` equals `val`, or is an array with an element equal to `val`
+fn contains(field: &Value, val: &Value) -> bool {
+    match field {
+        Value::Array(arr) if !val.is_array() => arr.iter().any(|v| equals(v, val)),
+        _ => equals(field, val),
+    }
+}
+
+fn compare_field<F>(doc: &Value, field: &str, val: &Value, f: F) -> bool
+where
+    F: Fn(Ordering) -> bool,
+{
+    lookup(doc, field)
+        .and_then(|v| compare(v, val))
+        .is_some_and(f)
+}
+
+/// Build a [Filter] from a Rust-like boolean expression over document fields.
+///
+/// Comparisons are `field op value` with `op` one of `==`, `!=`, `>`, `>=`, `<`, `<=`,
+/// where `field` is a dotted path of identifiers and `value` any expression that
+/// converts into a `serde_json::Value`. They can be combined with `&&`, `||`, `!`
+/// and parentheses; `&&` binds tighter than `||`.
+///
+/// ```
+/// use docdb::{filter, Filter};
+///
+/// let min_age = 30;
+/// let f = filter!(age > min_age && (address.city == "Berlin" || address.city == "Paris"));
+/// assert_eq!(
+///     f,
+///     Filter::gt("age", 30).and(Filter::eq("address.city", "Berlin").or(Filter::eq("address.city", "Paris")))
+/// );
+/// ```
+#[macro_export]
+macro_rules! filter {
+    ($($t:tt)+) => {
+        $crate::__filter_or!([] [] $($t)+)
+    };
+}
+
+/// Split the tokens of a `filter!` on `||`
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __filter_or {
+    ([] [$($cur:tt)+]) => {
+        $crate::__filter_and!([] [] $($cur)+)
+    };
+    ([$(($($alt:tt)+))+] [$($cur:tt)+]) => {
+        $crate::Filter::Or(vec![
+            $($crate::__filter_and!([] [] $($alt)+),)+
+            $crate::__filter_and!([] [] $($cur)+)
+        ])
+    };
+    ([$($alts:tt)*] [$($cur:tt)+] || $($rest:tt)+) => {
+        $crate::__filter_or!([$($alts)* ($($cur)+)] [] $($rest)+)
+    };
+    ([$($alts:tt)*] [$($cur:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__filter_or!([$($alts)*] [$($cur)* $next] $($rest)*)
+    };
+}
+
+/// Split the tokens of one `||` alternative of a `filter!` on `&&`
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __filter_and {
+    ([] [$($cur:tt)+]) => {
+        $crate::__filter_atom!($($cur)+)
+    };
+    ([$(($($term:tt)+))+] [$($cur:tt)+]) => {
+        $crate::Filter::And(vec![
+            $($crate::__filter_atom!($($term)+),)+
+            $crate::__filter_atom!($($cur)+)
+        ])
+    };
+    ([$($terms:tt)*] [$($cur:tt)+] && $($rest:tt)+) => {
+        $crate::__filter_and!([$($terms)* ($($cur)+)] [] $($rest)+)
+    };
+    ([$($terms:tt)*] [$($cur:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__filter_and!([$($terms)*] [$($cur)* $next] $($rest)*)
+    };
+}
+
+/// A single comparison, negation or parenthesized group of a `filter!`
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __filter_atom {
+    (($($inner:tt)+)) => {
+        $crate::filter!($($inner)+)
+    };
+    (! $($rest:tt)+) => {
+        $crate::Filter::not($crate::__filter_atom!($($rest)+))
+    };
+    ($($field:ident).+ == $val:expr) => {
+        $crate::Filter::eq(&[$(stringify!($field)),+].join("."), $val)
+    };
+    ($($field:ident).+ != $val:expr) => {
+        $crate::Filter::ne(&[$(stringify!($field)),+].join("."), $val)
+    };
+    ($($field:ident).+ >= $val:expr) => {
+        $crate::Filter::gte(&[$(stringify!($field)),+].join("."), $val)
+    };
+    ($($field:ident).+ <= $val:expr) => {
+        $crate::Filter::lte(&[$(stringify!($field)),+].join("."), $val)
+    };
+    ($($field:ident).+ > $val:expr) => {
+        $crate::Filter::gt(&[$(stringify!($field)),+].join("."), $val)
+    };
+    ($($field:ident).+ < $val:expr) => {
+        $crate::Filter::lt(&[$(stringify!($field)),+].join("."), $val)
+    };
+}
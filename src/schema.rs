@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::{DocError, Result};
+
+/// A JSON Schema attached to the keys of a keyspace that start with a prefix,
+/// stored in the db file
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct SchemaDef {
+    pub(crate) collection: Option<String>,
+    pub(crate) prefix: String,
+    /// the schema as JSON text, so that it can be stored with any serialization method
+    pub(crate) schema: String,
+}
+
+impl SchemaDef {
+    pub(crate) fn applies(&self, collection: Option<&str>, key: &str) -> bool {
+        self.collection.as_deref() == collection && key.starts_with(&self.prefix)
+    }
+}
+
+/// Keywords of JSON Schema that `validate` doesn't implement, so a schema using them
+/// is rejected rather than partially enforced
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "pattern",
+    "patternProperties",
+    "format",
+    "$ref",
+    "$dynamicRef",
+    "if",
+    "then",
+    "else",
+    "contains",
+    "minContains",
+    "maxContains",
+    "prefixItems",
+    "additionalItems",
+    "propertyNames",
+    "dependencies",
+    "dependentRequired",
+    "dependentSchemas",
+    "unevaluatedItems",
+    "unevaluatedProperties",
+];
+
+/// Check that `schema` and all of its subschemas can be used to validate documents
+pub(crate) fn check_schema(schema: &Value) -> Result<()> {
+    let schema = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(schema) => schema,
+        _ => {
+            return Err(DocError::Query(
+                "a JSON schema must be an object or a boolean".to_string(),
+            ))
+        }
+    };
+
+    if let Some(keyword) = UNSUPPORTED_KEYWORDS
+        .iter()
+        .find(|keyword| schema.contains_key(**keyword))
+    {
+        return Err(DocError::Query(format!(
+            "the JSON schema keyword {} isn't supported",
+            keyword
+        )));
+    }
+    for keyword in ["items", "additionalProperties", "not"] {
+        if let Some(subschema) = schema.get(keyword) {
+            check_schema(subschema)?;
+        }
+    }
+    if let Some(Value::Object(properties)) = schema.get("properties") {
+        for subschema in properties.values() {
+            check_schema(subschema)?;
+        }
+    }
+    for keyword in ["allOf", "anyOf", "oneOf"] {
+        if let Some(Value::Array(subschemas)) = schema.get(keyword) {
+            for subschema in subschemas {
+                check_schema(subschema)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Validate `doc` against `schema`, failing with the first violation found.
+///
+/// The schema must have passed `check_schema`.
+pub(crate) fn validate(schema: &Value, doc: &Value) -> Result<()> {
+    validate_at(schema, doc, "")
+}
+
+fn violation(path: &str, message: String) -> DocError {
+    DocError::SchemaViolation {
+        path: path.to_string(),
+        message,
+    }
+}
+
+fn validate_at(schema: &Value, doc: &Value, path: &str) -> Result<()> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(violation(path, "no value is allowed".to_string())),
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+
+    if let Some(types) = schema.get("type") {
+        check_type(types, doc, path)?;
+    }
+    if let Some(Value::Array(values)) = schema.get("enum") {
+        if !values.contains(doc) {
+            return Err(violation(
+                path,
+                format!("{} is not one of {:?}", doc, values),
+            ));
+        }
+    }
+    if let Some(value) = schema.get("const") {
+        if value != doc {
+            return Err(violation(path, format!("{} is not {}", doc, value)));
+        }
+    }
+
+    match doc {
+        Value::Number(n) => check_number(schema, n.as_f64().unwrap_or(f64::NAN), path)?,
+        Value::String(s) => {
+            check_length(schema, s.chars().count(), "minLength", "maxLength", path)?
+        }
+        Value::Array(arr) => check_array(schema, arr, path)?,
+        Value::Object(obj) => check_object(schema, obj, path)?,
+        _ => {}
+    }
+
+    if let Some(Value::Array(schemas)) = schema.get("allOf") {
+        for schema in schemas {
+            validate_at(schema, doc, path)?;
+        }
+    }
+    if let Some(Value::Array(schemas)) = schema.get("anyOf") {
+        if !schemas
+            .iter()
+            .any(|schema| validate_at(schema, doc, path).is_ok())
+        {
+            return Err(violation(path, "matches none of anyOf".to_string()));
+        }
+    }
+    if let Some(Value::Array(schemas)) = schema.get("oneOf") {
+        let matched = schemas
+            .iter()
+            .filter(|schema| validate_at(schema, doc, path).is_ok())
+            .count();
+        if matched != 1 {
+            return Err(violation(
+                path,
+                format!("matches {} of oneOf instead of one", matched),
+            ));
+        }
+    }
+    if let Some(schema) = schema.get("not") {
+        if validate_at(schema, doc, path).is_ok() {
+            return Err(violation(path, "matches the schema of not".to_string()));
+        }
+    }
+    Ok(())
+}
+
+fn type_name(doc: &Value) -> &'static str {
+    match doc {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn check_type(types: &Value, doc: &Value, path: &str) -> Result<()> {
+    let is = |name: &str| match name {
+        "integer" => match doc {
+            Value::Number(n) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            _ => false,
+        },
+        name => type_name(doc) == name,
+    };
+    let ok = match types {
+        Value::String(name) => is(name),
+        Value::Array(names) => names.iter().filter_map(Value::as_str).any(is),
+        _ => true,
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(violation(
+            path,
+            format!("expected type {}, found {}", types, type_name(doc)),
+        ))
+    }
+}
+
+fn check_number(schema: &Map<String, Value>, n: f64, path: &str) -> Result<()> {
+    let check = |keyword: &str, op: &str, ok: fn(f64, f64) -> bool| -> Result<()> {
+        match schema.get(keyword).and_then(Value::as_f64) {
+            Some(bound) if !ok(n, bound) => {
+                Err(violation(path, format!("{} is not {} {}", n, op, bound)))
+            }
+            _ => Ok(()),
+        }
+    };
+    check("minimum", ">=", |n, b| n >= b)?;
+    check("maximum", "<=", |n, b| n <= b)?;
+    check("exclusiveMinimum", ">", |n, b| n > b)?;
+    check("exclusiveMaximum", "<", |n, b| n < b)?;
+
+    if let Some(m) = schema.get("multipleOf").and_then(Value::as_f64) {
+        if m > 0.0 && (n / m).fract() != 0.0 {
+            return Err(violation(path, format!("{} is not a multiple of {}", n, m)));
+        }
+    }
+    Ok(())
+}
+
+fn check_length(
+    schema: &Map<String, Value>,
+    len: usize,
+    min: &str,
+    max: &str,
+    path: &str,
+) -> Result<()> {
+    if let Some(min_len) = schema.get(min).and_then(Value::as_u64) {
+        if (len as u64) < min_len {
+            return Err(violation(
+                path,
+                format!("length {} is less than {}", len, min_len),
+            ));
+        }
+    }
+    if let Some(max_len) = schema.get(max).and_then(Value::as_u64) {
+        if len as u64 > max_len {
+            return Err(violation(
+                path,
+                format!("length {} is more than {}", len, max_len),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_array(schema: &Map<String, Value>, arr: &[Value], path: &str) -> Result<()> {
+    check_length(schema, arr.len(), "minItems", "maxItems", path)?;
+    if let Some(items) = schema.get("items") {
+        for (i, item) in arr.iter().enumerate() {
+            validate_at(items, item, &format!("{}/{}", path, i))?;
+        }
+    }
+    if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+        for (i, item) in arr.iter().enumerate() {
+            if arr[..i].contains(item) {
+                return Err(violation(path, format!("{} is not unique", item)));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_object(schema: &Map<String, Value>, obj: &Map<String, Value>, path: &str) -> Result<()> {
+    check_length(schema, obj.len(), "minProperties", "maxProperties", path)?;
+    let member_path =
+        |name: &str| format!("{}/{}", path, name.replace('~', "~0").replace('/', "~1"));
+
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !obj.contains_key(name) {
+                return Err(violation(&member_path(name), "is required".to_string()));
+            }
+        }
+    }
+
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, val) in obj {
+        match properties.and_then(|props| props.get(name)) {
+            Some(schema) => validate_at(schema, val, &member_path(name))?,
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => {
+                    return Err(violation(
+                        &member_path(name),
+                        "is not an allowed property".to_string(),
+                    ))
+                }
+                Some(schema) => validate_at(schema, val, &member_path(name))?,
+                None => {}
+            },
+        }
+    }
+    Ok(())
+}
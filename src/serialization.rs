@@ -1,57 +1,320 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 use crate::error::{DocError, Result};
+use crate::index::{IndexDef, TextIndexDef, VectorIndexDef};
+use crate::schema::SchemaDef;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// A serialization format supplied by the user, see [DocDb::with_serializer](crate::DocDb::with_serializer).
+///
+/// Values go through [serde_json::Value] on their way to and from the format, so they
+/// must be representable as JSON: for instance, maps must have string keys.
+///
+/// A db file holds a single document, with the top-level keyspace under `data`, the
+/// collections under `collections` and some metadata. Each value is embedded in it,
+/// so by default the file is written and read with `serialize_data` and
+/// `deserialize_data` as well.
+///
+/// # Examples
+///
+/// ```
+/// use docdb::{error::Result, DocDb, DocSerializer, DumpPolicy};
+/// use serde_json::Value;
+///
+/// struct PrettyJson;
+///
+/// impl DocSerializer for PrettyJson {
+///     fn serialize_data(&self, val: &Value) -> Result<Vec<u8>> {
+///         Ok(serde_json::to_vec_pretty(val)?)
+///     }
+///
+///     fn deserialize_data(&self, ser_data: &[u8]) -> Result<Value> {
+///         Ok(serde_json::from_slice(ser_data)?)
+///     }
+/// }
+///
+/// let mut db = DocDb::with_serializer("example.db", DumpPolicy::NeverDump, Box::new(PrettyJson));
+/// db.set("key", &100).unwrap();
+/// assert_eq!(db.get::<i32>("key"), Some(100));
+/// ```
+pub trait DocSerializer: Send + Sync {
+    fn serialize_data(&self, val: &Value) -> Result<Vec<u8>>;
+
+    fn deserialize_data(&self, ser_data: &[u8]) -> Result<Value>;
+
+    fn serialize_db(&self, db: &Value) -> Result<Vec<u8>> {
+        self.serialize_data(db)
+    }
 
-#[allow(dead_code)]
-pub struct DocSerializer {}
-#[allow(dead_code)]
-impl DocSerializer {
-    fn serialize_data<V>(&self, data: &V) -> Result<Vec<u8>>
-    where
-        V: Serialize,
-    {
-        let v = serde_json::to_string(data)?;
-        Ok(v.into_bytes())
+    fn deserialize_db(&self, ser_db: &[u8]) -> Result<Value> {
+        self.deserialize_data(ser_db)
     }
+}
 
-    fn deserialize_data<V>(&self, data: &[u8]) -> Result<V>
-    where
-        V: DeserializeOwned,
-    {
-        let v = serde_json::from_str(std::str::from_utf8(data)?)?;
-        Ok(v)
+pub(crate) type DbMap = HashMap<String, Vec<u8>>;
+
+/// Version of the [DbFile] layout written by `serialize_db`.
+/// Files without it are the legacy flat map of key to value.
+const DB_FILE_VERSION: u32 = 1;
+
+/// Version of the [DbFile] layout written by the text serializers (JSON, YAML, TOML
+/// and RON), where values are embedded natively rather than as strings holding them
+const NESTED_DB_FILE_VERSION: u32 = 2;
+
+/// Everything held by a db file: the top-level keyspace and the named collections
+#[derive(Default)]
+pub(crate) struct DbData {
+    pub(crate) map: DbMap,
+    pub(crate) collections: HashMap<String, DbMap>,
+    /// the last id handed out by `DocDb::insert`
+    pub(crate) last_id: u64,
+    /// the version of the stored data, see `DocDb::load_with_migrations`
+    pub(crate) schema_version: u64,
+    pub(crate) indexes: Vec<IndexDef>,
+    pub(crate) text_indexes: Vec<TextIndexDef>,
+    pub(crate) vector_indexes: Vec<VectorIndexDef>,
+    pub(crate) schemas: Vec<SchemaDef>,
+    /// top-level keys whose value was stored as is by `DocDb::set_raw`
+    pub(crate) raw_keys: HashSet<String>,
+    /// the options the file was written with, which a loaded DB keeps using
+    pub(crate) options: SerializerOptions,
+}
+
+impl DbData {
+    fn from_map(map: DbMap) -> Self {
+        Self {
+            map,
+            ..Default::default()
+        }
     }
+}
 
-    fn serialize_db(&self, map: &HashMap<String, Vec<u8>>) -> Result<Vec<u8>> {
-        let db_map: HashMap<&String, &str> = map
-            .iter()
-            .map(|(k, v)| (k, std::str::from_utf8(v).unwrap()))
-            .collect();
+/// Options for writing db files, see [DocDb::set_serializer_options](crate::DocDb::set_serializer_options).
+///
+/// They only change how the file looks: a file written with any options loads
+/// the same way. They are stored in the file, so a loaded DB keeps writing it
+/// with the same options.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerializerOptions {
+    /// Indent the file, for the text formats that are written on a single line
+    /// otherwise (JSON, TOML and RON)
+    pub pretty: bool,
+    /// Write keys in sorted order, so that dumping the same data always gives
+    /// the same file. Otherwise the order changes from one dump to the next.
+    ///
+    /// This applies to keys, collection names, and the members of JSON/YAML
+    /// values embedded in the file.
+    pub sort_keys: bool,
+}
+
+/// Entries of a map in a db file, written in the order they are held
+struct Entries<V>(Vec<(String, V)>);
 
-        let ret = serde_json::to_string(&db_map)?;
-        Ok(ret.into_bytes())
+impl<V> Entries<V> {
+    fn new(mut entries: Vec<(String, V)>, sort_keys: bool) -> Self {
+        if sort_keys {
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        Self(entries)
     }
+}
 
-    fn deserialize_db(&self, db: &[u8]) -> Result<HashMap<String, Vec<u8>>> {
-        let db_data = std::str::from_utf8(db)?;
-        let data = serde_json::from_str::<HashMap<String, String>>(db_data)?;
+impl<V> Default for Entries<V> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<V: Serialize> Serialize for Entries<V> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+    }
+}
 
-        let hmap: HashMap<String, Vec<u8>> = data
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for Entries<V> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let map = HashMap::<String, V>::deserialize(deserializer)?;
+        Ok(Self(map.into_iter().collect()))
+    }
+}
+
+/// A value stored by `DocDb::set_raw`, written as bytes, or as a base64 string
+/// in the human-readable formats
+struct RawBytes(Vec<u8>);
+
+impl Serialize for RawBytes {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&BASE64.encode(&self.0))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RawBytes {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let encoded = String::deserialize(deserializer)?;
+            match BASE64.decode(encoded) {
+                Ok(bytes) => Ok(Self(bytes)),
+                Err(err) => Err(serde::de::Error::custom(err)),
+            }
+        } else {
+            deserializer.deserialize_byte_buf(RawBytesVisitor)
+        }
+    }
+}
+
+struct RawBytesVisitor;
+
+impl<'de> serde::de::Visitor<'de> for RawBytesVisitor {
+    type Value = RawBytes;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("bytes")
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> std::result::Result<RawBytes, E> {
+        Ok(RawBytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> std::result::Result<RawBytes, E> {
+        Ok(RawBytes(v))
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<RawBytes, A::Error> {
+        let mut bytes = Vec::new();
+        while let Some(b) = seq.next_element()? {
+            bytes.push(b);
+        }
+        Ok(RawBytes(bytes))
+    }
+}
+
+/// On-disk layout of a db file, generic over how each serialized value is written
+#[derive(Serialize, Deserialize)]
+struct DbFile<V> {
+    version: u32,
+    data: Entries<V>,
+    collections: Entries<Entries<V>>,
+    #[serde(default)]
+    last_id: u64,
+    #[serde(default)]
+    schema_version: u64,
+    #[serde(default)]
+    indexes: Vec<IndexDef>,
+    #[serde(default)]
+    text_indexes: Vec<TextIndexDef>,
+    #[serde(default)]
+    vector_indexes: Vec<VectorIndexDef>,
+    #[serde(default)]
+    schemas: Vec<SchemaDef>,
+    /// the top-level values stored by `DocDb::set_raw`, which are left out of `data`
+    #[serde(default)]
+    raw: Entries<RawBytes>,
+    #[serde(default)]
+    options: SerializerOptions,
+}
+
+impl<V> DbFile<V> {
+    fn from_data<'a, F>(data: &'a DbData, options: SerializerOptions, f: F) -> Result<Self>
+    where
+        F: Fn(&'a Vec<u8>) -> Result<V>,
+    {
+        let convert = |map: &'a DbMap, raw_keys: &HashSet<String>| -> Result<Entries<V>> {
+            let entries = map
+                .iter()
+                .filter(|(k, _)| !raw_keys.contains(*k))
+                .map(|(k, v)| Ok((k.to_string(), f(v)?)))
+                .collect::<Result<_>>()?;
+            Ok(Entries::new(entries, options.sort_keys))
+        };
+
+        let raw = data
+            .raw_keys
             .iter()
-            .map(|(k, v)| (k.to_string(), v.as_bytes().to_vec()))
+            .filter_map(|k| Some((k.to_string(), RawBytes(data.map.get(k)?.clone()))))
             .collect();
-        Ok(hmap)
+
+        let mut collections = Vec::new();
+        for (name, map) in data.collections.iter() {
+            collections.push((name.to_string(), convert(map, &HashSet::new())?));
+        }
+
+        Ok(Self {
+            version: DB_FILE_VERSION,
+            data: convert(&data.map, &data.raw_keys)?,
+            collections: Entries::new(collections, options.sort_keys),
+            last_id: data.last_id,
+            schema_version: data.schema_version,
+            indexes: data.indexes.clone(),
+            text_indexes: data.text_indexes.clone(),
+            vector_indexes: data.vector_indexes.clone(),
+            schemas: data.schemas.clone(),
+            raw: Entries::new(raw, options.sort_keys),
+            options,
+        })
     }
-}
 
-type DbMap = HashMap<String, Vec<u8>>;
+    fn into_data<F>(self, f: F) -> Result<DbData>
+    where
+        F: Fn(V) -> Result<Vec<u8>>,
+    {
+        let convert = |map: Entries<V>| -> Result<DbMap> {
+            map.0.into_iter().map(|(k, v)| Ok((k, f(v)?))).collect()
+        };
+
+        let mut collections = HashMap::new();
+        for (name, map) in self.collections.0 {
+            collections.insert(name, convert(map)?);
+        }
+
+        let mut map = convert(self.data)?;
+        let mut raw_keys = HashSet::new();
+        for (key, val) in self.raw.0 {
+            raw_keys.insert(key.clone());
+            map.insert(key, val.0);
+        }
+
+        Ok(DbData {
+            map,
+            collections,
+            last_id: self.last_id,
+            schema_version: self.schema_version,
+            indexes: self.indexes,
+            text_indexes: self.text_indexes,
+            vector_indexes: self.vector_indexes,
+            schemas: self.schemas,
+            raw_keys,
+            options: self.options,
+        })
+    }
+}
 
 /// An enum for specifying the serialization method to use when creating a new PickleDB database
 /// or loading one from a file
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SerializationMethod {
     /// [JSON serialization](https://crates.io/crates/serde_json)
     Json,
@@ -62,8 +325,17 @@ pub enum SerializationMethod {
     /// [YAML serialization](https://crates.io/crates/serde_yaml)
     Yaml,
 
-    /// [CBOR serialization](https://crates.io/crates/serde_cbor)
+    /// [CBOR serialization](https://crates.io/crates/ciborium)
     Cbor,
+
+    /// [MessagePack serialization](https://crates.io/crates/rmp-serde)
+    MessagePack,
+
+    /// [TOML serialization](https://crates.io/crates/toml)
+    Toml,
+
+    /// [RON serialization](https://crates.io/crates/ron)
+    Ron,
 }
 
 impl From<i32> for SerializationMethod {
@@ -73,6 +345,9 @@ impl From<i32> for SerializationMethod {
             1 => SerializationMethod::Bin,
             2 => SerializationMethod::Yaml,
             3 => SerializationMethod::Cbor,
+            4 => SerializationMethod::MessagePack,
+            5 => SerializationMethod::Toml,
+            6 => SerializationMethod::Ron,
             _ => SerializationMethod::Json,
         }
     }
@@ -86,6 +361,7 @@ impl fmt::Display for SerializationMethod {
 
 /// serde json for db
 #[cfg(feature = "json")]
+#[derive(Clone)]
 struct JsonSerializer {}
 
 #[cfg(feature = "json")]
@@ -104,47 +380,87 @@ impl JsonSerializer {
         }
     }
 
-    fn deserialize_data<V>(&self, ser_data: &[u8]) -> Option<V>
+    fn deserialize_data<V>(&self, ser_data: &[u8]) -> Result<V>
     where
         V: DeserializeOwned,
     {
-        match serde_json::from_str(std::str::from_utf8(ser_data).unwrap()) {
-            Ok(v) => Some(v),
-            Err(_err) => None,
+        match serde_json::from_str(std::str::from_utf8(ser_data)?) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(DocError::Deserialization(err.to_string())),
         }
     }
 
-    fn serialize_db(&self, map: &DbMap) -> Result<Vec<u8>> {
-        let json_map: HashMap<&String, &str> = map
-            .iter()
-            .map(|(k, v)| (k, std::str::from_utf8(v).unwrap()))
-            .collect();
-
-        match serde_json::to_string(&json_map) {
+    fn serialize_db(&self, data: &DbData, options: SerializerOptions) -> Result<Vec<u8>> {
+        let mut json_file = DbFile::from_data(data, options, |v| {
+            self.deserialize_data::<serde_json::Value>(v)
+        })?;
+        json_file.version = NESTED_DB_FILE_VERSION;
+
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&json_file)
+        } else {
+            serde_json::to_string(&json_file)
+        };
+        match json {
             Ok(v) => Ok(v.into_bytes()),
             Err(err) => Err(DocError::Serialization(err.to_string())),
         }
     }
 
-    pub fn deserialize_db(&self, ser_data: &[u8]) -> Result<DbMap> {
-        match serde_json::from_str::<HashMap<String, String>>(
-            std::str::from_utf8(ser_data).unwrap(),
-        ) {
+    pub fn deserialize_db(&self, ser_data: &[u8]) -> Result<DbData> {
+        let json_data = std::str::from_utf8(ser_data)?;
+        let json_value = serde_json::from_str::<serde_json::Value>(json_data)
+            .map_err(|err| DocError::Deserialization(err.to_string()))?;
+        // legacy files map each key to a string, only a DbFile has a numeric version
+        if json_value
+            .get("version")
+            .is_some_and(serde_json::Value::is_number)
+        {
+            let json_file = serde_json::from_value::<DbFile<serde_json::Value>>(json_value)
+                .map_err(|err| DocError::Deserialization(err.to_string()))?;
+            let nested = json_file.version >= NESTED_DB_FILE_VERSION;
+            return json_file.into_data(|v| match v {
+                // older files hold each value as a string of JSON
+                serde_json::Value::String(v) if !nested => Ok(v.into_bytes()),
+                v => self.serialize_data(&v),
+            });
+        }
+
+        // legacy file: a flat map of key to value
+        match serde_json::from_value::<HashMap<String, String>>(json_value) {
             Ok(json_map) => {
-                // let mut db_map = DbMap::new();
                 let db_map = json_map
-                    .iter()
-                    .map(|(k, v)| (k.to_string(), v.as_bytes().to_vec()))
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into_bytes()))
                     .collect();
-                Ok(db_map)
+                Ok(DbData::from_map(db_map))
             }
             Err(err) => Err(DocError::Deserialization(err.to_string())),
         }
     }
 }
 
+/// Sort the keys of the mappings in `val`, recursively
+#[cfg(feature = "yaml")]
+fn sort_yaml_keys(val: &mut serde_yaml::Value) {
+    match val {
+        serde_yaml::Value::Mapping(mapping) => {
+            let mut entries: Vec<_> = std::mem::take(mapping).into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            for (k, mut v) in entries {
+                sort_yaml_keys(&mut v);
+                mapping.insert(k, v);
+            }
+        }
+        serde_yaml::Value::Sequence(seq) => seq.iter_mut().for_each(sort_yaml_keys),
+        serde_yaml::Value::Tagged(tagged) => sort_yaml_keys(&mut tagged.value),
+        _ => {}
+    }
+}
+
 /// serde yaml for db
 #[cfg(feature = "yaml")]
+#[derive(Clone)]
 struct YamlSerializer {}
 
 #[cfg(feature = "yaml")]
@@ -163,43 +479,184 @@ impl YamlSerializer {
         }
     }
 
-    fn deserialize_data<V>(&self, ser_data: &[u8]) -> Option<V>
+    fn deserialize_data<V>(&self, ser_data: &[u8]) -> Result<V>
     where
         V: DeserializeOwned,
     {
-        match serde_yaml::from_str(std::str::from_utf8(ser_data).unwrap()) {
-            Ok(data) => Some(data),
-            Err(_err) => None,
+        match serde_yaml::from_str(std::str::from_utf8(ser_data)?) {
+            Ok(data) => Ok(data),
+            Err(err) => Err(DocError::Deserialization(err.to_string())),
         }
     }
 
-    fn serialize_db(&self, map: &DbMap) -> Result<Vec<u8>> {
-        let hmap: HashMap<&String, &str> = map
-            .iter()
-            .map(|(k, v)| (k, std::str::from_utf8(v).unwrap()))
-            .collect();
+    fn serialize_db(&self, data: &DbData, options: SerializerOptions) -> Result<Vec<u8>> {
+        let mut yaml_file = DbFile::from_data(data, options, |v| {
+            let mut val = self.deserialize_data::<serde_yaml::Value>(v)?;
+            if options.sort_keys {
+                sort_yaml_keys(&mut val);
+            }
+            Ok(val)
+        })?;
+        yaml_file.version = NESTED_DB_FILE_VERSION;
 
-        match serde_yaml::to_string(&hmap) {
+        match serde_yaml::to_string(&yaml_file) {
             Ok(d) => Ok(d.into_bytes()),
             Err(err) => Err(DocError::Serialization(err.to_string())),
         }
     }
 
-    fn deserialize_db(&self, db: &[u8]) -> Result<DbMap> {
-        match serde_yaml::from_str::<HashMap<String, String>>(std::str::from_utf8(db).unwrap()) {
+    fn deserialize_db(&self, db: &[u8]) -> Result<DbData> {
+        let yaml_data = std::str::from_utf8(db)?;
+        let yaml_value = serde_yaml::from_str::<serde_yaml::Value>(yaml_data)
+            .map_err(|err| DocError::Deserialization(err.to_string()))?;
+        // legacy files map each key to a string, only a DbFile has a numeric version
+        if yaml_value
+            .get("version")
+            .is_some_and(serde_yaml::Value::is_number)
+        {
+            let yaml_file = serde_yaml::from_value::<DbFile<serde_yaml::Value>>(yaml_value)
+                .map_err(|err| DocError::Deserialization(err.to_string()))?;
+            let nested = yaml_file.version >= NESTED_DB_FILE_VERSION;
+            return yaml_file.into_data(|v| match v {
+                // older files hold each value as a string of YAML
+                serde_yaml::Value::String(v) if !nested => Ok(v.into_bytes()),
+                v => self.serialize_data(&v),
+            });
+        }
+
+        // legacy file: a flat map of key to value
+        match serde_yaml::from_value::<HashMap<String, String>>(yaml_value) {
             Ok(data) => {
-                let db_map = data
-                    .iter()
-                    .map(|(k, v)| (k.to_string(), v.as_bytes().to_vec()))
-                    .collect();
-                Ok(db_map)
+                let db_map = data.into_iter().map(|(k, v)| (k, v.into_bytes())).collect();
+                Ok(DbData::from_map(db_map))
             }
             Err(err) => Err(DocError::Deserialization(err.to_string())),
         }
     }
 }
 
+/// serde toml for db
+///
+/// A TOML document must be a table, so values are written as TOML inline values
+/// (e.g. `{ x = 1, y = 2 }`), which also means they can't be `None` or `()`
+#[cfg(feature = "toml")]
+#[derive(Clone)]
+struct TomlSerializer {}
+
+#[cfg(feature = "toml")]
+impl TomlSerializer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn serialize_data<V>(&self, v: &V) -> Result<Vec<u8>>
+    where
+        V: Serialize,
+    {
+        let mut ser_data = String::new();
+        match v.serialize(toml::ser::ValueSerializer::new(&mut ser_data)) {
+            Ok(_) => Ok(ser_data.into_bytes()),
+            Err(err) => Err(DocError::Serialization(err.to_string())),
+        }
+    }
+
+    fn deserialize_data<V>(&self, ser_data: &[u8]) -> Result<V>
+    where
+        V: DeserializeOwned,
+    {
+        let ser_data = std::str::from_utf8(ser_data)?;
+        match V::deserialize(toml::de::ValueDeserializer::new(ser_data)) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(DocError::Deserialization(err.to_string())),
+        }
+    }
+
+    fn serialize_db(&self, data: &DbData, options: SerializerOptions) -> Result<Vec<u8>> {
+        let mut toml_file =
+            DbFile::from_data(data, options, |v| self.deserialize_data::<toml::Value>(v))?;
+        toml_file.version = NESTED_DB_FILE_VERSION;
+
+        let toml = if options.pretty {
+            toml::to_string_pretty(&toml_file)
+        } else {
+            toml::to_string(&toml_file)
+        };
+        match toml {
+            Ok(d) => Ok(d.into_bytes()),
+            Err(err) => Err(DocError::Serialization(err.to_string())),
+        }
+    }
+
+    fn deserialize_db(&self, db: &[u8]) -> Result<DbData> {
+        match toml::from_str::<DbFile<toml::Value>>(std::str::from_utf8(db)?) {
+            Ok(toml_file) => toml_file.into_data(|v| self.serialize_data(&v)),
+            Err(err) => Err(DocError::Deserialization(err.to_string())),
+        }
+    }
+}
+
+/// serde ron for db
+#[cfg(feature = "ron")]
+#[derive(Clone)]
+struct RonSerializer {}
+
+#[cfg(feature = "ron")]
+impl RonSerializer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn serialize_data<V>(&self, v: &V) -> Result<Vec<u8>>
+    where
+        V: Serialize,
+    {
+        match ron::to_string(v) {
+            Ok(ser_data) => Ok(ser_data.into_bytes()),
+            Err(err) => Err(DocError::Serialization(err.to_string())),
+        }
+    }
+
+    fn deserialize_data<V>(&self, ser_data: &[u8]) -> Result<V>
+    where
+        V: DeserializeOwned,
+    {
+        match ron::from_str(std::str::from_utf8(ser_data)?) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(DocError::Deserialization(err.to_string())),
+        }
+    }
+
+    fn serialize_db(&self, data: &DbData, options: SerializerOptions) -> Result<Vec<u8>> {
+        let mut ron_file =
+            DbFile::from_data(data, options, |v| {
+                match ron::value::RawValue::from_ron(std::str::from_utf8(v)?) {
+                    Ok(v) => Ok(v),
+                    Err(err) => Err(DocError::Serialization(err.to_string())),
+                }
+            })?;
+        ron_file.version = NESTED_DB_FILE_VERSION;
+
+        let ron = if options.pretty {
+            ron::ser::to_string_pretty(&ron_file, ron::ser::PrettyConfig::default())
+        } else {
+            ron::to_string(&ron_file)
+        };
+        match ron {
+            Ok(d) => Ok(d.into_bytes()),
+            Err(err) => Err(DocError::Serialization(err.to_string())),
+        }
+    }
+
+    fn deserialize_db(&self, db: &[u8]) -> Result<DbData> {
+        match ron::from_str::<DbFile<Box<ron::value::RawValue>>>(std::str::from_utf8(db)?) {
+            Ok(ron_file) => ron_file.into_data(|v| Ok(v.trim().get_ron().as_bytes().to_vec())),
+            Err(err) => Err(DocError::Deserialization(err.to_string())),
+        }
+    }
+}
+
 #[cfg(feature = "cbor")]
+#[derive(Clone)]
 struct CborSerializer {}
 #[cfg(feature = "cbor")]
 impl CborSerializer {
@@ -211,26 +668,83 @@ impl CborSerializer {
     where
         V: Serialize,
     {
-        todo!()
+        let mut ser_data = Vec::new();
+        match ciborium::into_writer(v, &mut ser_data) {
+            Ok(_) => Ok(ser_data),
+            Err(err) => Err(DocError::Serialization(err.to_string())),
+        }
     }
 
-    fn deserialize_data<V>(&self, v: &[u8]) -> Option<V>
+    fn deserialize_data<V>(&self, ser_data: &[u8]) -> Result<V>
     where
         V: DeserializeOwned,
     {
-        todo!()
+        match ciborium::from_reader(ser_data) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(DocError::Deserialization(err.to_string())),
+        }
     }
 
-    fn serialize_db(&self, map: &DbMap) -> Result<Vec<u8>> {
-        todo!()
+    /// Values are embedded in the file as CBOR items rather than as byte strings
+    fn serialize_db(&self, data: &DbData, options: SerializerOptions) -> Result<Vec<u8>> {
+        let cbor_file = DbFile::from_data(data, options, |v| {
+            self.deserialize_data::<ciborium::Value>(v)
+        })?;
+        self.serialize_data(&cbor_file)
     }
 
-    fn deserialize_db(&self, db: &[u8]) -> Result<DbMap> {
-        todo!()
+    fn deserialize_db(&self, db: &[u8]) -> Result<DbData> {
+        let cbor_file = self.deserialize_data::<DbFile<ciborium::Value>>(db)?;
+        cbor_file.into_data(|v| self.serialize_data(&v))
+    }
+}
+
+/// Structs are written as maps with named fields rather than as arrays, so that
+/// the file can be read without knowing the Rust types
+#[cfg(feature = "msgpack")]
+#[derive(Clone)]
+struct MsgPackSerializer {}
+#[cfg(feature = "msgpack")]
+impl MsgPackSerializer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn serialize_data<V>(&self, v: &V) -> Result<Vec<u8>>
+    where
+        V: Serialize,
+    {
+        match rmp_serde::to_vec_named(v) {
+            Ok(ser_data) => Ok(ser_data),
+            Err(err) => Err(DocError::Serialization(err.to_string())),
+        }
+    }
+
+    fn deserialize_data<V>(&self, ser_data: &[u8]) -> Result<V>
+    where
+        V: DeserializeOwned,
+    {
+        match rmp_serde::from_slice(ser_data) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(DocError::Deserialization(err.to_string())),
+        }
+    }
+
+    /// Values are embedded in the file as MessagePack objects rather than as binary blobs
+    fn serialize_db(&self, data: &DbData, options: SerializerOptions) -> Result<Vec<u8>> {
+        let msgpack_file =
+            DbFile::from_data(data, options, |v| self.deserialize_data::<rmpv::Value>(v))?;
+        self.serialize_data(&msgpack_file)
+    }
+
+    fn deserialize_db(&self, db: &[u8]) -> Result<DbData> {
+        let msgpack_file = self.deserialize_data::<DbFile<rmpv::Value>>(db)?;
+        msgpack_file.into_data(|v| self.serialize_data(&v))
     }
 }
 
 #[cfg(feature = "bincode")]
+#[derive(Clone)]
 struct BincodeSerializer {}
 #[cfg(feature = "bincode")]
 impl BincodeSerializer {
@@ -248,42 +762,54 @@ impl BincodeSerializer {
         }
     }
 
-    fn deserialize_data<V>(&self, v: &[u8]) -> Option<V>
+    fn deserialize_data<V>(&self, v: &[u8]) -> Result<V>
     where
         V: DeserializeOwned,
     {
         match bincode::deserialize(v) {
-            Ok(v) => Some(v),
-            Err(err) => {
-                println!("deserialize err: {}", err);
-                None
-            }
+            Ok(v) => Ok(v),
+            Err(err) => Err(DocError::Deserialization(err.to_string())),
         }
     }
 
-    fn serialize_db(&self, map: &DbMap) -> Result<Vec<u8>> {
-        self.serialize_data(map)
+    fn serialize_db(&self, data: &DbData, options: SerializerOptions) -> Result<Vec<u8>> {
+        self.serialize_data(&DbFile::from_data(data, options, Ok)?)
     }
 
-    fn deserialize_db(&self, db: &[u8]) -> Result<DbMap> {
+    fn deserialize_db(&self, db: &[u8]) -> Result<DbData> {
+        if let Ok(bin_file) = bincode::deserialize::<DbFile<Vec<u8>>>(db) {
+            return bin_file.into_data(Ok);
+        }
+
+        // legacy file: a flat map of key to value
         match self.deserialize_data(db) {
-            Some(map) => Ok(map),
-            None => Err(DocError::Deserialization(
+            Ok(map) => Ok(DbData::from_map(map)),
+            Err(_err) => Err(DocError::Deserialization(
                 "cannot deserialize from db".to_string(),
             )),
         }
     }
 }
 
+#[derive(Clone)]
 pub(crate) struct Serializer {
     ser_method: SerializationMethod,
+    /// takes the place of `ser_method` when set
+    custom: Option<Arc<dyn DocSerializer>>,
+    pub(crate) options: SerializerOptions,
 
     #[cfg(feature = "json")]
     json_serializer: JsonSerializer,
     #[cfg(feature = "yaml")]
     yaml_serializer: YamlSerializer,
+    #[cfg(feature = "toml")]
+    toml_serializer: TomlSerializer,
+    #[cfg(feature = "ron")]
+    ron_serializer: RonSerializer,
     #[cfg(feature = "cbor")]
     cbor_serializer: CborSerializer,
+    #[cfg(feature = "msgpack")]
+    msgpack_serializer: MsgPackSerializer,
     #[cfg(feature = "bincode")]
     bin_serializer: BincodeSerializer,
 }
@@ -291,30 +817,58 @@ pub(crate) struct Serializer {
 impl Serializer {
     pub(crate) fn new(ser_method: SerializationMethod) -> Self {
         Self {
-            ser_method: ser_method,
+            ser_method,
+            custom: None,
+            options: SerializerOptions::default(),
             #[cfg(feature = "json")]
             json_serializer: JsonSerializer::new(),
             #[cfg(feature = "yaml")]
             yaml_serializer: YamlSerializer::new(),
+            #[cfg(feature = "toml")]
+            toml_serializer: TomlSerializer::new(),
+            #[cfg(feature = "ron")]
+            ron_serializer: RonSerializer::new(),
             #[cfg(feature = "cbor")]
             cbor_serializer: CborSerializer::new(),
+            #[cfg(feature = "msgpack")]
+            msgpack_serializer: MsgPackSerializer::new(),
             #[cfg(feature = "bincode")]
             bin_serializer: BincodeSerializer::new(),
         }
     }
 
+    pub(crate) fn custom(custom: Box<dyn DocSerializer>) -> Self {
+        Self {
+            custom: Some(Arc::from(custom)),
+            ..Self::new(SerializationMethod::Json)
+        }
+    }
+
     pub fn serialize_data<V>(&self, v: &V) -> Result<Vec<u8>>
     where
         V: Serialize,
     {
+        if let Some(custom) = &self.custom {
+            return match serde_json::to_value(v) {
+                Ok(val) => custom.serialize_data(&val),
+                Err(err) => Err(DocError::Serialization(err.to_string())),
+            };
+        }
+
         #[allow(unreachable_patterns)]
         match self.ser_method {
             #[cfg(feature = "json")]
             SerializationMethod::Json => self.json_serializer.serialize_data(v),
             #[cfg(feature = "yaml")]
             SerializationMethod::Yaml => self.yaml_serializer.serialize_data(v),
+            #[cfg(feature = "toml")]
+            SerializationMethod::Toml => self.toml_serializer.serialize_data(v),
+            #[cfg(feature = "ron")]
+            SerializationMethod::Ron => self.ron_serializer.serialize_data(v),
             #[cfg(feature = "cbor")]
             SerializationMethod::Cbor => self.cbor_serializer.serialize_data(v),
+            #[cfg(feature = "msgpack")]
+            SerializationMethod::MessagePack => self.msgpack_serializer.serialize_data(v),
             #[cfg(feature = "bincode")]
             SerializationMethod::Bin => self.bin_serializer.serialize_data(v),
             #[cfg(feature = "json")]
@@ -332,14 +886,35 @@ impl Serializer {
     where
         T: DeserializeOwned,
     {
+        self.try_deserialize_data(ser_data).ok()
+    }
+
+    /// Like `deserialize_data`, but reports why the data couldn't be deserialized
+    pub(crate) fn try_deserialize_data<T>(&self, ser_data: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        if let Some(custom) = &self.custom {
+            return match serde_json::from_value(custom.deserialize_data(ser_data)?) {
+                Ok(v) => Ok(v),
+                Err(err) => Err(DocError::Deserialization(err.to_string())),
+            };
+        }
+
         #[allow(unreachable_patterns)]
         match self.ser_method {
             #[cfg(feature = "json")]
             SerializationMethod::Json => self.json_serializer.deserialize_data(ser_data),
             #[cfg(feature = "yaml")]
             SerializationMethod::Yaml => self.yaml_serializer.deserialize_data(ser_data),
+            #[cfg(feature = "toml")]
+            SerializationMethod::Toml => self.toml_serializer.deserialize_data(ser_data),
+            #[cfg(feature = "ron")]
+            SerializationMethod::Ron => self.ron_serializer.deserialize_data(ser_data),
             #[cfg(feature = "cbor")]
             SerializationMethod::Cbor => self.cbor_serializer.deserialize_data(ser_data),
+            #[cfg(feature = "msgpack")]
+            SerializationMethod::MessagePack => self.msgpack_serializer.deserialize_data(ser_data),
             #[cfg(feature = "bincode")]
             SerializationMethod::Bin => self.bin_serializer.deserialize_data(ser_data),
             #[cfg(feature = "json")]
@@ -347,29 +922,74 @@ impl Serializer {
         }
     }
 
-    pub(crate) fn serialize_db(&self, map: &DbMap) -> Result<Vec<u8>> {
+    /// Check that stored values can be read as JSON documents without knowing their
+    /// type, which bincode doesn't allow since it isn't self-describing
+    pub(crate) fn check_documents(&self) -> Result<()> {
+        if cfg!(feature = "bincode")
+            && self.custom.is_none()
+            && self.ser_method == SerializationMethod::Bin
+        {
+            return Err(DocError::Unsupported(
+                "bincode values can't be read as documents".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Deserialize a stored value as a JSON document, see `check_documents`
+    pub(crate) fn try_deserialize_doc(&self, ser_data: &[u8]) -> Result<Value> {
+        self.check_documents()?;
+        self.try_deserialize_data(ser_data)
+    }
+
+    pub(crate) fn serialize_db(&self, data: &DbData) -> Result<Vec<u8>> {
+        let options = self.options;
+        if let Some(custom) = &self.custom {
+            let file = DbFile::from_data(data, options, |v| custom.deserialize_data(v))?;
+            return custom.serialize_db(&serde_json::to_value(file)?);
+        }
+
         #[allow(unreachable_patterns)]
         match self.ser_method {
             #[cfg(feature = "json")]
-            SerializationMethod::Json => self.json_serializer.serialize_db(map),
+            SerializationMethod::Json => self.json_serializer.serialize_db(data, options),
             #[cfg(feature = "yaml")]
-            SerializationMethod::Yaml => self.yaml_serializer.serialize_db(map),
+            SerializationMethod::Yaml => self.yaml_serializer.serialize_db(data, options),
+            #[cfg(feature = "toml")]
+            SerializationMethod::Toml => self.toml_serializer.serialize_db(data, options),
+            #[cfg(feature = "ron")]
+            SerializationMethod::Ron => self.ron_serializer.serialize_db(data, options),
             #[cfg(feature = "cbor")]
-            SerializationMethod::Cbor => self.cbor_serializer.serialize_db(map),
+            SerializationMethod::Cbor => self.cbor_serializer.serialize_db(data, options),
+            #[cfg(feature = "msgpack")]
+            SerializationMethod::MessagePack => self.msgpack_serializer.serialize_db(data, options),
             #[cfg(feature = "bincode")]
-            SerializationMethod::Bin => self.bin_serializer.serialize_db(map),
-            _ => self.json_serializer.serialize_db(map),
+            SerializationMethod::Bin => self.bin_serializer.serialize_db(data, options),
+            _ => self.json_serializer.serialize_db(data, options),
         }
     }
-    pub(crate) fn deserialize_db(&self, v: &[u8]) -> Result<DbMap> {
+    pub(crate) fn deserialize_db(&self, v: &[u8]) -> Result<DbData> {
+        if let Some(custom) = &self.custom {
+            return match serde_json::from_value::<DbFile<Value>>(custom.deserialize_db(v)?) {
+                Ok(file) => file.into_data(|val| custom.serialize_data(&val)),
+                Err(err) => Err(DocError::Deserialization(err.to_string())),
+            };
+        }
+
         #[allow(unreachable_patterns)]
         match self.ser_method {
             #[cfg(feature = "json")]
             SerializationMethod::Json => self.json_serializer.deserialize_db(v),
             #[cfg(feature = "yaml")]
             SerializationMethod::Yaml => self.yaml_serializer.deserialize_db(v),
+            #[cfg(feature = "toml")]
+            SerializationMethod::Toml => self.toml_serializer.deserialize_db(v),
+            #[cfg(feature = "ron")]
+            SerializationMethod::Ron => self.ron_serializer.deserialize_db(v),
             #[cfg(feature = "cbor")]
             SerializationMethod::Cbor => self.cbor_serializer.deserialize_db(v),
+            #[cfg(feature = "msgpack")]
+            SerializationMethod::MessagePack => self.msgpack_serializer.deserialize_db(v),
             #[cfg(feature = "bincode")]
             SerializationMethod::Bin => self.bin_serializer.deserialize_db(v),
             #[cfg(feature = "json")]
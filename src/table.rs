@@ -0,0 +1,114 @@
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::collection::Collection;
+use crate::error::Result;
+use crate::id::Id;
+use crate::iterator::DocDbIterator;
+
+/// A typed view of a collection, returned by [DocDb::table](crate::DocDb::table).
+///
+/// Every value is read and written as a `T`. A stored value that can't be
+/// deserialized into a `T` is reported as a
+/// [DocError::Deserialization](crate::error::DocError::Deserialization).
+pub struct Table<'a, T> {
+    collection: Collection<'a>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> Table<'a, T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    pub(crate) fn new(collection: Collection<'a>) -> Self {
+        Self {
+            collection,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get the name of the underlying collection.
+    pub fn name(&self) -> &str {
+        self.collection.name()
+    }
+
+    /// Set `val` under `key`, replacing the previous value if there is one.
+    pub fn set(&mut self, key: &str, val: &T) -> Result<()> {
+        self.collection.set(key, val)
+    }
+
+    /// Store `val` under a newly generated [Id] and return it, see
+    /// [DocDb::insert](crate::DocDb::insert).
+    pub fn insert(&mut self, val: &T) -> Result<Id> {
+        self.collection.insert(val)
+    }
+
+    /// Store each of `vals` under a newly generated [Id], dumping once for all of them.
+    pub fn insert_many(&mut self, vals: &[T]) -> Result<Vec<Id>> {
+        self.collection.insert_many(vals)
+    }
+
+    /// Get the value of `key`, or `None` if the key doesn't exist.
+    pub fn get(&self, key: &str) -> Result<Option<T>> {
+        self.collection.try_get(key)
+    }
+
+    /// Modify the value of `key` in place with `f` and store the result.
+    ///
+    /// Returns `false` if the key doesn't exist.
+    pub fn update<F>(&mut self, key: &str, f: F) -> Result<bool>
+    where
+        F: FnOnce(&mut T),
+    {
+        match self.get(key)? {
+            Some(mut val) => {
+                f(&mut val);
+                self.set(key, &val)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn exist(&self, key: &str) -> bool {
+        self.collection.exist(key)
+    }
+
+    /// Get a vector of all the keys in the table.
+    pub fn get_all_keys(&self) -> Vec<String> {
+        self.collection.get_all_keys()
+    }
+
+    /// Get the total number of keys in the table.
+    pub fn total_nums(&self) -> usize {
+        self.collection.total_nums()
+    }
+
+    pub fn rem(&mut self, key: &str) -> Result<bool> {
+        self.collection.rem(key)
+    }
+
+    pub fn iter(&self) -> TableIterator<'_, T> {
+        TableIterator {
+            inner: self.collection.iter(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Iterator over the keys and values of a [Table].
+pub struct TableIterator<'a, T> {
+    inner: DocDbIterator<'a>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: DeserializeOwned> Iterator for TableIterator<'a, T> {
+    type Item = (&'a str, Result<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next()?;
+        Some((item.key, item.try_get_value()))
+    }
+}
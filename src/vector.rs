@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::error::{DocError, Result};
+use crate::index::VectorIndexDef;
+use crate::query::lookup;
+use crate::serialization::{DbMap, Serializer};
+
+/// Number of k-means rounds used to train the lists of a vector index
+const TRAINING_ROUNDS: usize = 10;
+
+/// How to measure the similarity of two vectors in [DocDb::nearest](crate::DocDb::nearest)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// Cosine similarity, higher is closer
+    Cosine,
+    /// Dot product, higher is closer
+    Dot,
+    /// Euclidean distance, lower is closer
+    L2,
+}
+
+impl Metric {
+    /// Get the score of `v` against `query`
+    pub(crate) fn score(&self, query: &[f32], v: &[f32]) -> f32 {
+        match self {
+            Metric::Cosine => {
+                let norm = dot(query, query).sqrt() * dot(v, v).sqrt();
+                if norm == 0.0 {
+                    0.0
+                } else {
+                    dot(query, v) / norm
+                }
+            }
+            Metric::Dot => dot(query, v),
+            Metric::L2 => l2(query, v),
+        }
+    }
+
+    /// Sort `scored` best first, breaking ties by key, and keep the first `k`
+    pub(crate) fn rank(&self, mut scored: Vec<(String, f32)>, k: usize) -> Vec<(String, f32)> {
+        scored.sort_by(|(a_key, a), (b_key, b)| {
+            let ord = match self {
+                Metric::L2 => a.total_cmp(b),
+                _ => b.total_cmp(a),
+            };
+            ord.then_with(|| a_key.cmp(b_key))
+        });
+        scored.truncate(k);
+        scored
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn l2(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// Get the vector stored in `field` of `doc`, if it is an array of numbers
+pub(crate) fn embedding(doc: &Value, field: &str) -> Option<Vec<f32>> {
+    match lookup(doc, field)? {
+        Value::Array(arr) => arr.iter().map(|v| Some(v.as_f64()? as f32)).collect(),
+        _ => None,
+    }
+}
+
+/// Check that a query vector can be compared to stored vectors
+pub(crate) fn check_query(query: &[f32], k: usize) -> Result<()> {
+    if query.is_empty() || k == 0 {
+        return Err(DocError::Query(
+            "nearest needs a non-empty query vector and k > 0".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// An approximate nearest-neighbor index of a vector field of the documents of a
+/// collection (IVF: inverted file).
+///
+/// Vectors are partitioned into lists around centroids trained with k-means, and a
+/// search only scores the vectors of the lists whose centroids are the closest to
+/// the query. Vectors written after the index is built join the list of their
+/// closest centroid.
+pub(crate) struct VectorIndex {
+    pub(crate) def: VectorIndexDef,
+    centroids: Vec<Vec<f32>>,
+    /// one list per centroid: key -> vector
+    lists: Vec<HashMap<String, Vec<f32>>>,
+}
+
+impl VectorIndex {
+    /// Index all the documents of `map`, training the centroids on their vectors
+    pub(crate) fn build(def: VectorIndexDef, map: &DbMap, serializer: &Serializer) -> Result<Self> {
+        let mut vectors = Vec::new();
+        for (key, val) in map.iter() {
+            let doc = serializer.try_deserialize_doc(val)?;
+            if let Some(v) = embedding(&doc, &def.field) {
+                vectors.push((key.to_string(), v));
+            }
+        }
+        vectors.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut index = VectorIndex {
+            centroids: train(&vectors, def.lists),
+            lists: Vec::new(),
+            def,
+        };
+        index.lists = vec![HashMap::new(); index.centroids.len()];
+        for (key, v) in vectors {
+            index.add(key, v);
+        }
+        Ok(index)
+    }
+
+    /// Get the position of the centroid closest to `v`
+    fn closest(&self, v: &[f32]) -> Option<usize> {
+        self.centroids
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, l2(c, v)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    fn add(&mut self, key: String, v: Vec<f32>) {
+        if let Some(dims) = self.centroids.first().map(Vec::len) {
+            if dims != v.len() {
+                return;
+            }
+        }
+        // until there are as many lists as wanted, every new vector starts one
+        if self.centroids.len() < self.def.lists {
+            self.centroids.push(v.clone());
+            self.lists.push(HashMap::new());
+        }
+        if let Some(i) = self.closest(&v) {
+            self.lists[i].insert(key, v);
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, doc: &Value) {
+        if let Some(v) = embedding(doc, &self.def.field) {
+            self.add(key.to_string(), v);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) {
+        // centroids added since the vector was indexed may be closer to it now
+        for list in self.lists.iter_mut() {
+            list.remove(key);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.centroids.clear();
+        self.lists.clear();
+    }
+
+    /// Get the `k` vectors closest to `query` among the lists of the `probes`
+    /// centroids closest to it
+    pub(crate) fn nearest(&self, k: usize, query: &[f32], metric: Metric) -> Vec<(String, f32)> {
+        let mut centroids: Vec<(usize, f32)> = self
+            .centroids
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.len() == query.len())
+            .map(|(i, c)| (i, l2(c, query)))
+            .collect();
+        centroids.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        let scored = centroids
+            .iter()
+            .take(self.def.probes.max(1))
+            .flat_map(|(i, _)| self.lists[*i].iter())
+            .map(|(key, v)| (key.clone(), metric.score(query, v)))
+            .collect();
+        metric.rank(scored, k)
+    }
+}
+
+/// Train up to `lists` centroids over `vectors` with k-means, starting from evenly
+/// spaced vectors so that building the same data always gives the same index
+fn train(vectors: &[(String, Vec<f32>)], lists: usize) -> Vec<Vec<f32>> {
+    let dims = match vectors.first() {
+        Some((_, v)) => v.len(),
+        None => return Vec::new(),
+    };
+    let vectors: Vec<&Vec<f32>> = vectors
+        .iter()
+        .map(|(_, v)| v)
+        .filter(|v| v.len() == dims)
+        .collect();
+    let lists = lists.min(vectors.len());
+    let mut centroids: Vec<Vec<f32>> = (0..lists)
+        .map(|i| vectors[i * vectors.len() / lists].clone())
+        .collect();
+
+    for _ in 0..TRAINING_ROUNDS {
+        let mut sums = vec![vec![0.0; dims]; lists];
+        let mut counts = vec![0usize; lists];
+        for v in vectors.iter() {
+            let closest = centroids
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (i, l2(c, v)))
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(i, _)| i)
+                .expect("there is at least one centroid");
+            for (sum, x) in sums[closest].iter_mut().zip(v.iter()) {
+                *sum += x;
+            }
+            counts[closest] += 1;
+        }
+        for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+            // a centroid without vectors keeps its place
+            if count > 0 {
+                *centroid = sum.into_iter().map(|x| x / count as f32).collect();
+            }
+        }
+    }
+    centroids
+}
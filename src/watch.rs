@@ -0,0 +1,53 @@
+use std::sync::mpsc::Sender;
+
+use serde::de::DeserializeOwned;
+
+use crate::serialization::Serializer;
+
+/// What happened to a key, see [Change]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// The key was set, either created or overwritten
+    Set,
+    /// The key was removed
+    Removed,
+}
+
+/// A change of a key, sent to the receivers returned by [DocDb::subscribe](crate::DocDb::subscribe)
+/// once the change has been applied.
+pub struct Change {
+    /// The collection of the key, `None` for a top-level key
+    pub collection: Option<String>,
+    pub key: String,
+    pub kind: ChangeKind,
+    pub(crate) old: Option<Vec<u8>>,
+    pub(crate) new: Option<Vec<u8>>,
+    pub(crate) serializer: Serializer,
+}
+
+impl Change {
+    /// Get the value of the key before the change, `None` if it didn't exist or
+    /// can't be deserialized into `T`.
+    pub fn get_old<T: DeserializeOwned>(&self) -> Option<T> {
+        self.serializer.deserialize_data(self.old.as_ref()?)
+    }
+
+    /// Get the value of the key after the change, `None` if it was removed or
+    /// can't be deserialized into `T`.
+    pub fn get_new<T: DeserializeOwned>(&self) -> Option<T> {
+        self.serializer.deserialize_data(self.new.as_ref()?)
+    }
+}
+
+/// A receiver of the changes of the keys of a keyspace that start with a prefix
+pub(crate) struct Subscriber {
+    pub(crate) collection: Option<String>,
+    pub(crate) prefix: String,
+    pub(crate) sender: Sender<Change>,
+}
+
+impl Subscriber {
+    pub(crate) fn wants(&self, collection: Option<&str>, key: &str) -> bool {
+        self.collection.as_deref() == collection && key.starts_with(&self.prefix)
+    }
+}
@@ -0,0 +1,78 @@
+use docdb::{filter, DocDb, DumpPolicy, SerializationMethod};
+use serde_json::json;
+
+mod common;
+
+#[test]
+fn test_aggregate_collection() {
+    set_test_src!("aggregate_coll.db");
+
+    let mut db = DocDb::new(
+        "aggregate_coll.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    db.collection("orders")
+        .insert_many(&[
+            json!({"customer": "ann", "total": 10, "shipped": true}),
+            json!({"customer": "bob", "total": 2.5, "shipped": false}),
+            json!({"customer": "ann", "total": 30, "shipped": true}),
+            json!({"customer": "cid", "note": "no total"}),
+        ])
+        .unwrap();
+
+    let orders = db.collection("orders");
+    let agg = orders.aggregate();
+    assert_eq!(agg.count(), 4);
+    assert_eq!(agg.count_where(&filter!(shipped == true)).unwrap(), 2);
+    assert_eq!(agg.sum("total").unwrap(), 42.5);
+    assert_eq!(agg.avg("total").unwrap(), Some(42.5 / 3.0));
+    assert_eq!(agg.avg("missing").unwrap(), None);
+    assert_eq!(agg.min::<f64>("total").unwrap(), Some(2.5));
+    assert_eq!(agg.max::<u32>("total").unwrap(), Some(30));
+    assert_eq!(agg.max::<String>("customer").unwrap().unwrap(), "cid");
+
+    let groups = agg.group_by::<String>("customer").unwrap();
+    let counts: Vec<(String, usize)> = groups
+        .into_iter()
+        .map(|(customer, items)| (customer, items.len()))
+        .collect();
+    assert_eq!(
+        counts,
+        vec![
+            ("ann".to_string(), 2),
+            ("bob".to_string(), 1),
+            ("cid".to_string(), 1)
+        ]
+    );
+}
+
+#[test]
+fn test_aggregate_key_prefix() {
+    set_test_src!("aggregate_prefix.db");
+
+    let mut db = DocDb::new(
+        "aggregate_prefix.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    db.set("user:1", &json!({"age": 30, "city": "Paris"}))
+        .unwrap();
+    db.set("user:2", &json!({"age": 20, "city": "Oslo"}))
+        .unwrap();
+    db.set("user:3", &json!({"age": 40, "city": "Paris"}))
+        .unwrap();
+    db.set("order:1", &json!({"age": 1000})).unwrap();
+
+    let users = db.aggregate("user:");
+    assert_eq!(users.count(), 3);
+    assert_eq!(users.sum("age").unwrap(), 90.0);
+    assert_eq!(users.min::<u8>("age").unwrap(), Some(20));
+
+    let groups = users.group_by::<String>("city").unwrap();
+    assert_eq!(groups[0].0, "Oslo");
+    let keys: Vec<&str> = groups[1].1.iter().map(|item| item.get_key()).collect();
+    assert_eq!(keys, vec!["user:1", "user:3"]);
+
+    assert_eq!(db.aggregate("").count(), 4);
+}
@@ -0,0 +1,181 @@
+use docdb::{DocDb, DumpPolicy, SerializationMethod};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct User {
+    name: String,
+    age: u32,
+}
+
+#[test]
+fn test_collection_isolation() {
+    set_test_src!("collection_isolation.db");
+
+    let mut db = DocDb::new(
+        "collection_isolation.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+
+    db.set("alice", &"top-level").unwrap();
+    db.collection("users")
+        .set(
+            "alice",
+            &User {
+                name: "Alice".to_string(),
+                age: 30,
+            },
+        )
+        .unwrap();
+    db.collection("admins").set("root", &1).unwrap();
+
+    // every keyspace only sees its own keys
+    assert_eq!(db.total_nums(), 1);
+    assert_eq!(db.get::<String>("alice").unwrap(), "top-level");
+    assert_eq!(db.collection("users").total_nums(), 1);
+    assert_eq!(db.collection("users").get::<User>("alice").unwrap().age, 30);
+    assert!(!db.collection("users").exist("root"));
+    assert_eq!(db.collection("admins").get_all_keys(), vec!["root"]);
+
+    // collections are persisted in the same file
+    let mut read_db =
+        DocDb::load_read_only("collection_isolation.db", SerializationMethod::Json).unwrap();
+    let mut names = read_db.list_collections();
+    names.sort();
+    assert_eq!(names, vec!["admins", "users"]);
+    assert_eq!(read_db.get::<String>("alice").unwrap(), "top-level");
+
+    let users = read_db.collection("users");
+    assert_eq!(
+        users.get::<User>("alice").unwrap(),
+        User {
+            name: "Alice".to_string(),
+            age: 30
+        }
+    );
+    let keys: Vec<String> = users.iter().map(|kv| kv.get_key().to_string()).collect();
+    assert_eq!(keys, vec!["alice"]);
+}
+
+#[test]
+fn test_rem_clear_and_drop_collection() {
+    set_test_src!("collection_drop.db");
+
+    let mut db = DocDb::new(
+        "collection_drop.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+
+    {
+        let mut logs = db.collection("logs");
+        for i in 0..5 {
+            logs.set(&format!("log{}", i), &i).unwrap();
+        }
+        assert!(logs.rem("log0").unwrap());
+        assert!(!logs.rem("log0").unwrap());
+        assert_eq!(logs.total_nums(), 4);
+
+        logs.clear().unwrap();
+        assert_eq!(logs.total_nums(), 0);
+    }
+    db.collection("users").set("bob", &1).unwrap();
+
+    // an empty collection is still listed until it is dropped
+    assert!(db.list_collections().contains(&"logs".to_string()));
+    assert!(db.drop_collection("logs").unwrap());
+    assert!(!db.drop_collection("logs").unwrap());
+    assert_eq!(db.list_collections(), vec!["users"]);
+
+    let read_db = DocDb::load_read_only("collection_drop.db", SerializationMethod::Json).unwrap();
+    assert_eq!(read_db.list_collections(), vec!["users"]);
+}
+
+#[test]
+fn test_read_missing_collection() {
+    set_test_src!("collection_missing.db");
+
+    let mut db = DocDb::new(
+        "collection_missing.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    db.collection("users").set("bob", &1).unwrap();
+
+    {
+        let mut logs = db.collection("logs");
+        assert!(logs.get::<i32>("log0").is_none());
+        assert_eq!(logs.total_nums(), 0);
+        assert_eq!(logs.iter().count(), 0);
+        assert_eq!(logs.iter_inserted().count(), 0);
+        assert!(!logs.rem("log0").unwrap());
+        logs.clear().unwrap();
+    }
+
+    // neither reading nor removing from a missing collection creates it
+    assert_eq!(db.list_collections(), vec!["users"]);
+    let read_db =
+        DocDb::load_read_only("collection_missing.db", SerializationMethod::Json).unwrap();
+    assert_eq!(read_db.list_collections(), vec!["users"]);
+}
+
+#[test]
+fn test_typed_table() {
+    set_test_src!("typed_table.db");
+
+    let mut db = DocDb::new(
+        "typed_table.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+
+    {
+        let mut users = db.table::<User>("users");
+        users
+            .set(
+                "alice",
+                &User {
+                    name: "Alice".to_string(),
+                    age: 30,
+                },
+            )
+            .unwrap();
+        assert!(users.update("alice", |user| user.age += 1).unwrap());
+        assert!(!users.update("bob", |user| user.age += 1).unwrap());
+
+        assert_eq!(users.get("alice").unwrap().unwrap().age, 31);
+        assert!(users.get("bob").unwrap().is_none());
+    }
+
+    // a value of the wrong shape is an error, not a silent None
+    db.collection("users")
+        .set("broken", &vec![1, 2, 3])
+        .unwrap();
+    let users = db.table::<User>("users");
+    assert!(users.get("broken").is_err());
+
+    let mut seen = 0;
+    for (key, user) in users.iter() {
+        match key {
+            "alice" => assert_eq!(user.unwrap().name, "Alice"),
+            "broken" => assert!(user.is_err()),
+            _ => panic!(),
+        }
+        seen += 1;
+    }
+    assert_eq!(seen, 2);
+
+    // documents without a natural key get a generated id
+    let mut users = db.table::<User>("users");
+    let user = |name: &str| User {
+        name: name.to_string(),
+        age: 20,
+    };
+    let carol = users.insert(&user("Carol")).unwrap();
+    let ids = users.insert_many(&[user("Dan"), user("Erin")]).unwrap();
+    assert!(carol < ids[0] && ids[0] < ids[1]);
+    assert_eq!(users.get(&carol.key()).unwrap().unwrap().name, "Carol");
+    assert_eq!(users.total_nums(), 5);
+}
@@ -1,3 +1,10 @@
+#![allow(
+    dead_code,
+    unused_imports,
+    clippy::crate_in_macro_def,
+    clippy::empty_docs
+)]
+
 use docdb::SerializationMethod;
 use std::fs;
 use std::path::Path;
@@ -0,0 +1,100 @@
+use docdb::error::DocError;
+use docdb::{Compression, DocDb, DumpPolicy, SerializationMethod};
+use serde_json::json;
+
+mod common;
+
+fn fill(db: &mut DocDb) {
+    for i in 0..200 {
+        db.set(
+            &format!("user{}", i),
+            &json!({"name": "same name", "role": "admin", "active": true, "id": i}),
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_compressed_dump_and_load() {
+    let plain_name = "test_compression_plain.db";
+    set_test_src!(plain_name);
+    let mut db = DocDb::new_json(plain_name, DumpPolicy::DumpRelyRequest);
+    fill(&mut db);
+    db.dump().unwrap();
+    let plain_len = std::fs::metadata(plain_name).unwrap().len();
+
+    for (compression, codec) in [(Compression::Zstd(0), 1), (Compression::Lz4, 2)] {
+        let db_name = format!("test_compression_{:?}.db", compression);
+        set_test_src!(&db_name);
+
+        let mut db = DocDb::new_json(&db_name, DumpPolicy::DumpRelyRequest);
+        db.set_compression(compression);
+        fill(&mut db);
+        db.dump().unwrap();
+
+        let content = std::fs::read(&db_name).unwrap();
+        assert_eq!(&content[..5], &[b'D', b'D', b'B', b'Z', codec]);
+        assert!((content.len() as u64) * 3 < plain_len);
+
+        // a loaded DB keeps compressing its file
+        let mut db = DocDb::load_json(&db_name, DumpPolicy::AutoDump).unwrap();
+        assert_eq!(db.get::<serde_json::Value>("user7").unwrap()["id"], 7);
+        db.rem("user7").unwrap();
+        assert!(std::fs::read(&db_name).unwrap().starts_with(b"DDBZ"));
+
+        let read_db = DocDb::load_read_only(&db_name, SerializationMethod::Json).unwrap();
+        assert_eq!(read_db.total_nums(), 199);
+    }
+}
+
+#[test]
+fn test_zstd_level_is_kept_on_load() {
+    let db_name = "test_compression_zstd_level.db";
+    set_test_src!(db_name);
+    let header = |level: i32| [&b"DDBZ\x01"[..], &level.to_le_bytes()].concat();
+
+    let mut db = DocDb::new_json(db_name, DumpPolicy::DumpRelyRequest);
+    db.set_compression(Compression::Zstd(19));
+    fill(&mut db);
+    db.dump().unwrap();
+    assert!(std::fs::read(db_name).unwrap().starts_with(&header(19)));
+
+    // a loaded DB compresses its file at the same level
+    let mut db = DocDb::load_json(db_name, DumpPolicy::DumpRelyRequest).unwrap();
+    db.dump().unwrap();
+    assert!(std::fs::read(db_name).unwrap().starts_with(&header(19)));
+
+    db.set_compression(Compression::Zstd(-5));
+    db.dump().unwrap();
+    let db = DocDb::load_json(db_name, DumpPolicy::NeverDump).unwrap();
+    assert!(std::fs::read(db_name).unwrap().starts_with(&header(-5)));
+    assert_eq!(db.total_nums(), 200);
+}
+
+#[test]
+fn test_compression_errors() {
+    let db_name = "test_compression_errors.db";
+    set_test_src!(db_name);
+
+    let mut db = DocDb::new_json(db_name, DumpPolicy::AutoDump);
+    db.set_compression(Compression::Lz4);
+    fill(&mut db);
+
+    // uncompressing back
+    db.set_compression(Compression::None);
+    db.dump().unwrap();
+    assert!(std::fs::read(db_name).unwrap().starts_with(b"{"));
+
+    for content in [
+        &b"DDBZ\x02garbage"[..],
+        b"DDBZ\x01\x03",
+        b"DDBZ\x09",
+        b"DDBZ",
+    ] {
+        std::fs::write(db_name, content).unwrap();
+        assert!(matches!(
+            DocDb::load_read_only(db_name, SerializationMethod::Json),
+            Err(DocError::Deserialization(_))
+        ));
+    }
+}
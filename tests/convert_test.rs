@@ -0,0 +1,131 @@
+use docdb::error::DocError;
+use docdb::{DocDb, DumpPolicy, SerializationMethod};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Coor {
+    x: i32,
+    y: i32,
+}
+
+#[test]
+fn test_convert() {
+    let src_name = "test_convert_src.db";
+    let dst_name = "test_convert_dst.db";
+    set_test_src!(src_name);
+    set_test_src!(dst_name);
+
+    let mut db = DocDb::new_json(src_name, DumpPolicy::AutoDump);
+    db.set("num", &10).unwrap();
+    db.set("string", &"my string").unwrap();
+    db.set("struct", &Coor { x: 1, y: 2 }).unwrap();
+    db.collection("coords")
+        .set("c1", &Coor { x: 3, y: 4 })
+        .unwrap();
+    db.create_index("coords", "x").unwrap();
+
+    DocDb::convert(
+        src_name,
+        SerializationMethod::Json,
+        dst_name,
+        SerializationMethod::Yaml,
+    )
+    .unwrap();
+
+    let mut read_db = DocDb::load_yaml(dst_name, DumpPolicy::NeverDump).unwrap();
+    assert_eq!(read_db.get::<i32>("num"), Some(10));
+    assert_eq!(read_db.get::<String>("string").unwrap(), "my string");
+    assert_eq!(read_db.get::<Coor>("struct"), Some(Coor { x: 1, y: 2 }));
+    let coords = read_db.collection("coords");
+    assert_eq!(coords.get::<Coor>("c1"), Some(Coor { x: 3, y: 4 }));
+    assert_eq!(coords.find(&docdb::Filter::eq("x", 3)).unwrap().len(), 1);
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Reading {
+    value: f32,
+    coor: Coor,
+    note: Option<i32>,
+    missing: Option<i32>,
+}
+
+fn check_values(db: &DocDb) {
+    assert_eq!(db.get::<i32>("num"), Some(10));
+    assert_eq!(db.get::<f32>("float"), Some(2.5));
+    assert_eq!(db.get::<Coor>("struct"), Some(Coor { x: 1, y: 2 }));
+    assert_eq!(db.get::<Option<i32>>("some"), Some(Some(5)));
+    assert_eq!(db.get::<Option<i32>>("none"), Some(None));
+    assert_eq!(
+        db.get::<Reading>("reading"),
+        Some(Reading {
+            value: 2.5,
+            coor: Coor { x: 1, y: 2 },
+            note: Some(5),
+            missing: None,
+        })
+    );
+    assert_eq!(db.get_raw("blob"), Some(&[0xff, 0x00][..]));
+}
+
+#[test]
+fn test_change_serialization() {
+    let db_name = "test_change_serialization.db";
+    let bin_name = "test_change_serialization_bin.db";
+    set_test_src!(db_name);
+    set_test_src!(bin_name);
+
+    let mut db = DocDb::new_json(db_name, DumpPolicy::AutoDump);
+    db.set("num", &10).unwrap();
+    db.set("float", &2.5f32).unwrap();
+    db.set("struct", &Coor { x: 1, y: 2 }).unwrap();
+    db.set("some", &Some(5)).unwrap();
+    db.set("none", &None::<i32>).unwrap();
+    db.set(
+        "reading",
+        &Reading {
+            value: 2.5,
+            coor: Coor { x: 1, y: 2 },
+            note: Some(5),
+            missing: None,
+        },
+    )
+    .unwrap();
+    db.set_raw("blob", &[0xff, 0x00]).unwrap();
+
+    // values converted into bincode couldn't be read back as their types
+    assert!(matches!(
+        db.change_serialization(SerializationMethod::Bin),
+        Err(DocError::Unsupported(_))
+    ));
+    assert!(matches!(
+        DocDb::convert(
+            db_name,
+            SerializationMethod::Json,
+            bin_name,
+            SerializationMethod::Bin
+        ),
+        Err(DocError::Unsupported(_))
+    ));
+    assert!(!std::path::Path::new(bin_name).exists());
+    check_values(&db);
+
+    // a round trip through YAML keeps every value
+    db.change_serialization(SerializationMethod::Yaml).unwrap();
+    check_values(&db);
+    let mut db = DocDb::load_yaml(db_name, DumpPolicy::AutoDump).unwrap();
+    check_values(&db);
+    db.change_serialization(SerializationMethod::Json).unwrap();
+    let read_db = DocDb::load_json(db_name, DumpPolicy::NeverDump).unwrap();
+    check_values(&read_db);
+
+    // bincode values can't be read without knowing their types
+    let mut db = DocDb::new_bincode(bin_name, DumpPolicy::AutoDump);
+    db.set("string", &"my string").unwrap();
+    assert!(matches!(
+        db.change_serialization(SerializationMethod::Json),
+        Err(DocError::Unsupported(_))
+    ));
+    assert_eq!(db.get::<String>("string").unwrap(), "my string");
+}
@@ -0,0 +1,190 @@
+use docdb::error::DocError;
+use docdb::{DocDb, DumpPolicy, PatchOp, SerializationMethod};
+use serde_json::json;
+
+mod common;
+
+#[test]
+fn test_get_and_set_path() {
+    set_test_src!("document_path.db");
+
+    let mut db = DocDb::new(
+        "document_path.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    db.set(
+        "config",
+        &json!({"server": {"host": "localhost", "port": 80}, "tags": ["a", "b"], "a/b": 1}),
+    )
+    .unwrap();
+
+    assert_eq!(db.get_path::<u16>("config", "/server/port"), Some(80));
+    assert_eq!(db.get_path::<String>("config", "/tags/1").unwrap(), "b");
+    assert_eq!(db.get_path::<i32>("config", "/a~1b"), Some(1));
+    assert_eq!(db.get_path::<u16>("config", "/server/missing"), None);
+    assert_eq!(db.get_path::<u16>("missing", "/server/port"), None);
+
+    db.set_path("config", "/server/port", &8080).unwrap();
+    db.set_path("config", "/server/tls", &true).unwrap();
+    db.set_path("config", "/tags/-", &"c").unwrap();
+    db.set_path("config", "/tags/0", &"z").unwrap();
+
+    // the changes are persisted
+    let db = DocDb::load_json("document_path.db", DumpPolicy::NeverDump).unwrap();
+    assert_eq!(
+        db.get::<serde_json::Value>("config").unwrap(),
+        json!({"server": {"host": "localhost", "port": 8080, "tls": true}, "tags": ["z", "b", "c"], "a/b": 1})
+    );
+}
+
+#[test]
+fn test_set_path_errors() {
+    set_test_src!("document_path_err.db");
+
+    let mut db = DocDb::new(
+        "document_path_err.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    db.collection("servers")
+        .set("web", &json!({"ports": [80]}))
+        .unwrap();
+
+    let mut servers = db.collection("servers");
+    for (key, path) in [
+        ("missing", "/ports"),
+        ("web", "ports"),
+        ("web", "/missing/port"),
+        ("web", "/ports/5"),
+        ("web", "/ports/0/x"),
+    ] {
+        assert!(matches!(
+            servers.set_path(key, path, &1),
+            Err(DocError::Path(_))
+        ));
+    }
+    assert_eq!(
+        servers.get_path::<Vec<u16>>("web", "/ports"),
+        Some(vec![80])
+    );
+}
+
+#[test]
+fn test_merge_patch() {
+    set_test_src!("document_merge.db");
+
+    let mut db = DocDb::new(
+        "document_merge.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    db.set(
+        "post",
+        &json!({"title": "Hello", "author": {"name": "Ann", "email": "ann@x"}, "tags": ["a"]}),
+    )
+    .unwrap();
+
+    db.merge_patch(
+        "post",
+        &json!({"title": "Hi", "author": {"email": null}, "tags": ["b"], "draft": {"x": null}}),
+    )
+    .unwrap();
+    assert_eq!(
+        db.get::<serde_json::Value>("post").unwrap(),
+        json!({"title": "Hi", "author": {"name": "Ann"}, "tags": ["b"], "draft": {}})
+    );
+
+    assert!(matches!(
+        db.merge_patch("missing", &json!({"a": 1})),
+        Err(DocError::Path(_))
+    ));
+}
+
+#[test]
+fn test_apply_patch() {
+    set_test_src!("document_patch.db");
+
+    let mut db = DocDb::new(
+        "document_patch.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    db.collection("posts")
+        .set(
+            "1",
+            &json!({"title": "Hello", "tags": ["a", "c"], "meta": {}}),
+        )
+        .unwrap();
+
+    let ops: Vec<PatchOp> = serde_json::from_value(json!([
+        {"op": "test", "path": "/title", "value": "Hello"},
+        {"op": "add", "path": "/tags/1", "value": "b"},
+        {"op": "replace", "path": "/title", "value": "Hi"},
+        {"op": "copy", "from": "/title", "path": "/meta/old_title"},
+        {"op": "move", "from": "/tags/0", "path": "/tags/-"},
+        {"op": "remove", "path": "/meta/old_title"},
+    ]))
+    .unwrap();
+    db.collection("posts").apply_patch("1", &ops).unwrap();
+    assert_eq!(
+        db.collection("posts")
+            .get::<serde_json::Value>("1")
+            .unwrap(),
+        json!({"title": "Hi", "tags": ["b", "c", "a"], "meta": {}})
+    );
+
+    // a failing operation leaves the document unchanged
+    let ops = vec![
+        PatchOp::Remove {
+            path: "/meta".to_string(),
+        },
+        PatchOp::Test {
+            path: "/title".to_string(),
+            value: json!("Hello"),
+        },
+    ];
+    assert!(matches!(
+        db.collection("posts").apply_patch("1", &ops),
+        Err(DocError::Patch(_))
+    ));
+    // numbers are compared by value, including inside arrays and objects
+    db.collection("posts")
+        .set_path("1", "/meta", &json!({"score": 1, "ranks": [2, 3]}))
+        .unwrap();
+    let ops = vec![
+        PatchOp::Test {
+            path: "/meta/score".to_string(),
+            value: json!(1.0),
+        },
+        PatchOp::Test {
+            path: "/meta".to_string(),
+            value: json!({"ranks": [2.0, 3], "score": 1.0}),
+        },
+    ];
+    db.collection("posts").apply_patch("1", &ops).unwrap();
+    let ops = vec![PatchOp::Test {
+        path: "/meta/ranks".to_string(),
+        value: json!([2.5, 3]),
+    }];
+    assert!(matches!(
+        db.collection("posts").apply_patch("1", &ops),
+        Err(DocError::Patch(_))
+    ));
+    db.collection("posts")
+        .set_path("1", "/meta", &json!({}))
+        .unwrap();
+    let ops = vec![PatchOp::Replace {
+        path: "/missing".to_string(),
+        value: json!(1),
+    }];
+    assert!(matches!(
+        db.collection("posts").apply_patch("1", &ops),
+        Err(DocError::Path(_))
+    ));
+    assert_eq!(
+        db.collection("posts")
+            .get_path::<serde_json::Value>("1", "/meta"),
+        Some(json!({}))
+    );
+}
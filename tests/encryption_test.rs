@@ -0,0 +1,115 @@
+use docdb::error::DocError;
+use docdb::{DocDb, DumpPolicy, SerializationMethod};
+
+mod common;
+
+const KEY: [u8; 32] = [7; 32];
+
+#[test]
+fn test_encrypted_dump_and_load() {
+    let db_name = "test_encrypted.db";
+    set_test_src!(db_name);
+
+    let mut db = DocDb::new_json(db_name, DumpPolicy::AutoDump);
+    db.set_encryption_key(Some(&KEY));
+    db.set("token", &"secret-token").unwrap();
+
+    let content = std::fs::read(db_name).unwrap();
+    assert!(content.starts_with(b"DDBX"));
+    assert!(!content
+        .windows(b"secret-token".len())
+        .any(|w| w == b"secret-token"));
+
+    let read_db = DocDb::load_encrypted(
+        db_name,
+        DumpPolicy::NeverDump,
+        SerializationMethod::Json,
+        &KEY,
+    )
+    .unwrap();
+    assert_eq!(read_db.get::<String>("token").unwrap(), "secret-token");
+
+    // wrong key
+    assert!(matches!(
+        DocDb::load_encrypted(
+            db_name,
+            DumpPolicy::NeverDump,
+            SerializationMethod::Json,
+            &[8; 32]
+        ),
+        Err(DocError::Decryption(_))
+    ));
+    // no key
+    assert!(matches!(
+        DocDb::load_read_only(db_name, SerializationMethod::Json),
+        Err(DocError::Decryption(_))
+    ));
+    // tampering
+    let mut tampered = content.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    std::fs::write(db_name, tampered).unwrap();
+    assert!(matches!(
+        DocDb::load_encrypted(
+            db_name,
+            DumpPolicy::NeverDump,
+            SerializationMethod::Json,
+            &KEY
+        ),
+        Err(DocError::Decryption(_))
+    ));
+    // a file that isn't encrypted
+    std::fs::write(db_name, r#"{"token":"\"secret-token\""}"#).unwrap();
+    assert!(matches!(
+        DocDb::load_encrypted(
+            db_name,
+            DumpPolicy::NeverDump,
+            SerializationMethod::Json,
+            &KEY
+        ),
+        Err(DocError::Decryption(_))
+    ));
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_encryption_with_compression() {
+    let db_name = "test_encrypted_compressed.db";
+    set_test_src!(db_name);
+
+    let mut db = DocDb::new_json(db_name, DumpPolicy::AutoDump);
+    db.set_compression(docdb::Compression::Zstd(0));
+    db.set_encryption_key(Some(&KEY));
+    for i in 0..100 {
+        db.set(&format!("token{}", i), &"secret-token").unwrap();
+    }
+    let content = std::fs::read(db_name).unwrap();
+    db.dump().unwrap();
+    // a new nonce for every dump
+    assert_ne!(std::fs::read(db_name).unwrap(), content);
+
+    // a loaded DB keeps encrypting and compressing its file
+    let mut db = DocDb::load_encrypted(
+        db_name,
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+        &KEY,
+    )
+    .unwrap();
+    assert!(db.rem("token0").unwrap());
+    let read_db = DocDb::load_encrypted(
+        db_name,
+        DumpPolicy::NeverDump,
+        SerializationMethod::Json,
+        &KEY,
+    )
+    .unwrap();
+    assert_eq!(read_db.total_nums(), 99);
+
+    // decrypting the file back
+    db.set_encryption_key(None);
+    db.dump().unwrap();
+    assert!(std::fs::read(db_name).unwrap().starts_with(b"DDBZ"));
+    let read_db = DocDb::load_read_only(db_name, SerializationMethod::Json).unwrap();
+    assert_eq!(read_db.total_nums(), 99);
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::approx_constant)]
+
 use fs2::FileExt;
 use std::fs::File;
 
@@ -92,3 +94,33 @@ fn test_dump() {
     // unlock the file
     db_file.unlock().unwrap();
 }
+
+#[test]
+fn test_documents_unsupported_with_bincode() {
+    set_test_src!("bincode_documents.db");
+
+    let mut db = DocDb::new_bincode("bincode_documents.db", DumpPolicy::NeverDump);
+    let is_unsupported = |err: error::DocError| matches!(err, error::DocError::Unsupported(_));
+
+    // rejected up front, even before there is anything to read
+    assert!(is_unsupported(
+        db.find(&docdb::Filter::exists("name")).err().unwrap()
+    ));
+    assert!(is_unsupported(
+        db.query().fetch::<serde_json::Value>().err().unwrap()
+    ));
+    assert!(is_unsupported(db.aggregate("").sum("age").err().unwrap()));
+
+    db.set("user", &serde_json::json!({"name": "Alice", "age": 30}))
+        .unwrap();
+    assert_eq!(db.get_path::<String>("user", "/name"), None);
+    assert!(is_unsupported(
+        db.set_path("user", "/age", &31).err().unwrap()
+    ));
+    assert!(is_unsupported(
+        db.collection("users")
+            .find(&docdb::Filter::exists("name"))
+            .err()
+            .unwrap()
+    ));
+}
@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+
+use docdb::error::DocError;
+use docdb::{DocDb, DumpPolicy, SerializationMethod};
+use serde_json::json;
+
+mod common;
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn test_db_with_hooks_is_send() {
+    assert_send::<DocDb>();
+}
+
+#[test]
+fn test_before_set_modifies_or_rejects() {
+    set_test_src!("hooks_before_set.db");
+
+    let mut db = DocDb::new(
+        "hooks_before_set.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    db.on_before_set("user:", |_key, doc| {
+        if doc.get("name").is_none() {
+            return Err(DocError::Rejected("a user needs a name".to_string()));
+        }
+        doc["updated_at"] = json!(42);
+        Ok(())
+    });
+
+    db.set("user:1", &json!({"name": "ann"})).unwrap();
+    assert_eq!(
+        db.get::<serde_json::Value>("user:1").unwrap(),
+        json!({"name": "ann", "updated_at": 42})
+    );
+
+    // a rejected write leaves the previous value
+    assert!(matches!(
+        db.set("user:1", &json!({"age": 3})),
+        Err(DocError::Rejected(_))
+    ));
+    assert_eq!(db.get_path::<String>("user:1", "/name").unwrap(), "ann");
+
+    // keys outside the prefix don't run the hook
+    db.set("post:1", &json!({"title": "hi"})).unwrap();
+    assert_eq!(db.get_path::<u32>("post:1", "/updated_at"), None);
+
+    // writes built on set run it too
+    db.set_path("user:1", "/name", &"bob").unwrap();
+    assert_eq!(db.get_path::<u32>("user:1", "/updated_at"), Some(42));
+}
+
+#[test]
+fn test_after_set_and_before_rem() {
+    set_test_src!("hooks_after_set.db");
+
+    let mut db = DocDb::new(
+        "hooks_after_set.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let mut orders = db.collection("orders");
+    orders.on_after_set("", move |key, doc| {
+        log.lock().unwrap().push(key.to_string());
+        match doc["total"].as_i64() {
+            Some(total) if total < 0 => Err(DocError::Rejected("negative total".to_string())),
+            _ => Ok(()),
+        }
+    });
+    orders.on_before_rem("", |_key, doc| match doc["paid"].as_bool() {
+        Some(true) => Err(DocError::Rejected("paid orders are kept".to_string())),
+        _ => Ok(()),
+    });
+
+    orders.set("a", &json!({"total": 5, "paid": true})).unwrap();
+    assert!(orders.set("a", &json!({"total": -1})).is_err());
+    assert!(orders.insert(&json!({"total": -2})).is_err());
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec!["a", "a", "00000000000000000001"]
+    );
+
+    // the rejected writes were rolled back
+    assert_eq!(orders.get_all_keys(), vec!["a"]);
+    assert_eq!(orders.get_path::<i64>("a", "/total"), Some(5));
+
+    assert!(matches!(orders.rem("a"), Err(DocError::Rejected(_))));
+    orders
+        .set("b", &json!({"total": 1, "paid": false}))
+        .unwrap();
+    assert!(orders.rem("b").unwrap());
+    assert!(!orders.rem("missing").unwrap());
+
+    // the db file holds the rolled back state
+    drop(orders);
+    drop(db);
+    let mut db = DocDb::load_json("hooks_after_set.db", DumpPolicy::NeverDump).unwrap();
+    assert_eq!(db.collection("orders").get_all_keys(), vec!["a"]);
+}
@@ -0,0 +1,116 @@
+use docdb::error::{DocError, ErrorType};
+use docdb::{filter, DocDb, DumpPolicy, SerializationMethod};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct User {
+    name: String,
+    email: String,
+}
+
+fn user(name: &str, email: &str) -> User {
+    User {
+        name: name.to_string(),
+        email: email.to_string(),
+    }
+}
+
+fn find_names(db: &mut DocDb, email: &str) -> Vec<String> {
+    let users = db.collection("users");
+    let mut names: Vec<String> = users
+        .find(&filter!(email == email))
+        .unwrap()
+        .iter()
+        .map(|kv| kv.get_value::<User>().unwrap().name)
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_index_follows_writes() {
+    set_test_src!("index_writes.db");
+
+    let mut db = DocDb::new(
+        "index_writes.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    db.collection("users")
+        .set("anna", &user("anna", "a@example.com"))
+        .unwrap();
+    db.create_index("users", "email").unwrap();
+
+    db.collection("users")
+        .set("ben", &user("ben", "a@example.com"))
+        .unwrap();
+    assert_eq!(find_names(&mut db, "a@example.com"), vec!["anna", "ben"]);
+
+    // overwriting and removing documents updates the index
+    db.collection("users")
+        .set("anna", &user("anna", "anna@example.com"))
+        .unwrap();
+    assert!(db.collection("users").rem("ben").unwrap());
+    assert!(find_names(&mut db, "a@example.com").is_empty());
+    assert_eq!(find_names(&mut db, "anna@example.com"), vec!["anna"]);
+
+    assert!(db.drop_index("users", "email").unwrap());
+    assert!(!db.drop_index("users", "email").unwrap());
+    assert_eq!(find_names(&mut db, "anna@example.com"), vec!["anna"]);
+}
+
+#[test]
+fn test_unique_index() {
+    set_test_src!("index_unique.db");
+
+    {
+        let mut db = DocDb::new(
+            "index_unique.db",
+            DumpPolicy::AutoDump,
+            SerializationMethod::Json,
+        );
+        db.create_unique_index("users", "email").unwrap();
+
+        let mut users = db.collection("users");
+        users.set("anna", &user("anna", "a@example.com")).unwrap();
+        // rewriting a document with its own value is fine
+        users.set("anna", &user("Anna", "a@example.com")).unwrap();
+
+        let err = users.set("ben", &user("ben", "a@example.com")).unwrap_err();
+        assert!(matches!(err, DocError::UniqueViolation(_)));
+        assert!(matches!(err.get_type(), ErrorType::Validation));
+        assert!(!users.exist("ben"));
+
+        // a batch is rejected as a whole
+        assert!(users
+            .insert_many(&[user("carl", "c@example.com"), user("dora", "c@example.com")])
+            .is_err());
+        assert_eq!(users.total_nums(), 1);
+    }
+
+    // the index definition is persisted and the index rebuilt on load
+    let mut db = DocDb::load(
+        "index_unique.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    )
+    .unwrap();
+    assert!(db
+        .collection("users")
+        .set("ben", &user("ben", "a@example.com"))
+        .is_err());
+    assert_eq!(find_names(&mut db, "a@example.com"), vec!["Anna"]);
+
+    // existing duplicates prevent creating a unique index
+    db.create_index("users", "email").unwrap();
+    db.collection("users")
+        .set("ben", &user("ben", "a@example.com"))
+        .unwrap();
+    let err = db.create_unique_index("users", "email").unwrap_err();
+    assert!(matches!(err, DocError::UniqueViolation(_)));
+
+    // and the existing index is kept
+    assert_eq!(find_names(&mut db, "a@example.com"), vec!["Anna", "ben"]);
+}
@@ -0,0 +1,81 @@
+use docdb::{DocDb, DumpPolicy, Id, SerializationMethod};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Note {
+    text: String,
+}
+
+fn note(text: &str) -> Note {
+    Note {
+        text: text.to_string(),
+    }
+}
+
+#[test]
+fn test_insert_generates_ids() {
+    set_test_src!("insert_ids.db");
+
+    let mut db = DocDb::new(
+        "insert_ids.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+
+    let first = db.insert(&note("first")).unwrap();
+    let more = db.insert_many(&[note("second"), note("third")]).unwrap();
+    assert_eq!(more.len(), 2);
+    assert!(first < more[0] && more[0] < more[1]);
+
+    // the id is the key the document is stored under
+    assert_eq!(db.get::<Note>(&first.key()).unwrap(), note("first"));
+    assert_eq!(first.key().parse::<Id>().unwrap(), first);
+
+    // keys that weren't inserted are skipped, the rest come in insertion order
+    db.set("config", &1).unwrap();
+    db.set("7", &note("user key")).unwrap();
+    db.set("+5", &note("user key")).unwrap();
+    let texts: Vec<String> = db
+        .iter_inserted()
+        .map(|kv| kv.get_value::<Note>().unwrap().text)
+        .collect();
+    assert_eq!(texts, vec!["first", "second", "third"]);
+    assert!("7".parse::<Id>().is_err());
+    assert!("+0000000000000000001".parse::<Id>().is_err());
+    assert!("99999999999999999999".parse::<Id>().is_err());
+}
+
+#[test]
+fn test_insert_id_counter_is_persisted() {
+    set_test_src!("insert_counter.db");
+
+    let last = {
+        let mut db = DocDb::new(
+            "insert_counter.db",
+            DumpPolicy::AutoDump,
+            SerializationMethod::Json,
+        );
+        let id = db.collection("notes").insert(&note("a")).unwrap();
+        // removing the newest document doesn't make its id available again
+        assert!(db.collection("notes").rem(&id.key()).unwrap());
+        id
+    };
+
+    let mut db = DocDb::load(
+        "insert_counter.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    )
+    .unwrap();
+    let mut notes = db.collection("notes");
+    let ids = notes.insert_many(&[note("b"), note("c")]).unwrap();
+    assert!(ids.iter().all(|id| *id > last));
+
+    let texts: Vec<String> = notes
+        .iter_inserted()
+        .map(|kv| kv.get_value::<Note>().unwrap().text)
+        .collect();
+    assert_eq!(texts, vec!["b", "c"]);
+}
@@ -1,4 +1,10 @@
-use docdb::{DocDb, DumpPolicy, SerializationMethod};
+#![allow(
+    clippy::approx_constant,
+    clippy::needless_borrows_for_generic_args,
+    clippy::useless_vec
+)]
+
+use docdb::{DocDb, DumpPolicy, SerializationMethod, SerializerOptions};
 use serde::{Deserialize, Serialize};
 
 mod common;
@@ -201,6 +207,189 @@ fn test_special_string() {
     );
 }
 
+#[cfg(feature = "cbor")]
+#[test]
+fn test_load_get_cbor() {
+    test_setup!("test_load_get", 3, db_name);
+
+    let mut db = DocDb::new(&db_name, DumpPolicy::AutoDump, ser_method!(3));
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Coor {
+        x: i32,
+        y: i32,
+    }
+    let mycoor = Coor { x: 1, y: 2 };
+
+    db.set("num_test", &10).unwrap();
+    db.set("float_test", &2.5).unwrap();
+    db.set("vec_test", &vec![1, 2, 3]).unwrap();
+    db.set("struct", &mycoor).unwrap();
+    db.set("string1", &String::from("\"double_quotes\""))
+        .unwrap();
+    db.set("string2", &String::from("שָׁלוֹם")).unwrap();
+    db.set("string3", &String::from("😻\nescapes\t\r")).unwrap();
+    db.collection("coords").set("c1", &mycoor).unwrap();
+
+    let mut read_db = DocDb::load_read_only(&db_name, ser_method!(3)).unwrap();
+    assert_eq!(read_db.get::<i32>("num_test").unwrap(), 10);
+    assert_eq!(read_db.get::<f32>("float_test").unwrap(), 2.5);
+    assert_eq!(read_db.get::<Vec<i32>>("vec_test").unwrap(), vec![1, 2, 3]);
+    assert_eq!(read_db.get::<Coor>("struct").unwrap(), mycoor);
+    assert_eq!(
+        read_db.get::<String>("string1").unwrap(),
+        String::from("\"double_quotes\"")
+    );
+    assert_eq!(
+        read_db.get::<String>("string2").unwrap(),
+        String::from("שָׁלוֹם")
+    );
+    assert_eq!(
+        read_db.get::<String>("string3").unwrap(),
+        String::from("😻\nescapes\t\r")
+    );
+    assert_eq!(read_db.total_nums(), 7);
+    assert_eq!(read_db.get::<String>("num_test"), None);
+    assert_eq!(
+        read_db.collection("coords").get::<Coor>("c1").unwrap(),
+        mycoor
+    );
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn test_load_get_msgpack() {
+    test_setup!("test_load_get", 4, db_name);
+
+    let mut db = DocDb::new_msgpack(&db_name, DumpPolicy::AutoDump);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Coor {
+        x: i32,
+        y: i32,
+    }
+    let mycoor = Coor { x: 1, y: 2 };
+
+    db.set("num_test", &10).unwrap();
+    db.set("float_test", &2.5).unwrap();
+    db.set("string_test", &String::from("שָׁלוֹם 😻\n")).unwrap();
+    db.set("vec_test", &vec![1, 2, 3]).unwrap();
+    db.set("struct", &mycoor).unwrap();
+
+    let read_db = DocDb::load_msgpack(&db_name, DumpPolicy::NeverDump).unwrap();
+    assert_eq!(read_db.get::<i32>("num_test").unwrap(), 10);
+    assert_eq!(read_db.get::<f32>("float_test").unwrap(), 2.5);
+    assert_eq!(
+        read_db.get::<String>("string_test").unwrap(),
+        String::from("שָׁלוֹם 😻\n")
+    );
+    assert_eq!(read_db.get::<Vec<i32>>("vec_test").unwrap(), vec![1, 2, 3]);
+    assert_eq!(read_db.get::<Coor>("struct").unwrap(), mycoor);
+
+    // the file is plain msgpack, with values embedded and struct fields named
+    let file: serde_json::Value = rmp_serde::from_slice(&std::fs::read(&db_name).unwrap()).unwrap();
+    assert_eq!(file["data"]["struct"], serde_json::json!({"x": 1, "y": 2}));
+    assert_eq!(file["data"]["vec_test"], serde_json::json!([1, 2, 3]));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn test_load_get_toml() {
+    test_setup!("test_load_get", 5, db_name);
+
+    let mut db = DocDb::new_toml(&db_name, DumpPolicy::AutoDump);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Coor {
+        x: i32,
+        y: i32,
+    }
+    let mycoor = Coor { x: 1, y: 2 };
+
+    db.set("num_test", &10).unwrap();
+    db.set("float_test", &2.5).unwrap();
+    db.set("string_test", &String::from("\"quotes\" שָׁלוֹם 😻\n"))
+        .unwrap();
+    db.set("vec_test", &vec![1, 2, 3]).unwrap();
+    db.set("struct", &mycoor).unwrap();
+    db.collection("coords").set("c1", &mycoor).unwrap();
+    db.create_index("coords", "x").unwrap();
+
+    let mut read_db = DocDb::load_toml(&db_name, DumpPolicy::NeverDump).unwrap();
+    assert_eq!(read_db.get::<i32>("num_test").unwrap(), 10);
+    assert_eq!(read_db.get::<f32>("float_test").unwrap(), 2.5);
+    assert_eq!(
+        read_db.get::<String>("string_test").unwrap(),
+        String::from("\"quotes\" שָׁלוֹם 😻\n")
+    );
+    assert_eq!(read_db.get::<Vec<i32>>("vec_test").unwrap(), vec![1, 2, 3]);
+    assert_eq!(read_db.get::<Coor>("struct").unwrap(), mycoor);
+    assert_eq!(
+        read_db.collection("coords").get::<Coor>("c1").unwrap(),
+        mycoor
+    );
+    assert_eq!(read_db.get::<String>("num_test"), None);
+
+    // the file is meant to be edited by hand, values are embedded as TOML
+    let content = std::fs::read_to_string(&db_name).unwrap();
+    assert!(content.contains("num_test = 10"));
+    assert!(content.contains("[data.struct]\nx = 1\ny = 2"));
+    std::fs::write(&db_name, content.replace("num_test = 10", "num_test = 11")).unwrap();
+    let read_db = DocDb::load_toml(&db_name, DumpPolicy::NeverDump).unwrap();
+    assert_eq!(read_db.get::<i32>("num_test").unwrap(), 11);
+}
+
+#[cfg(feature = "ron")]
+#[test]
+fn test_load_get_ron() {
+    test_setup!("test_load_get", 6, db_name);
+
+    let mut db = DocDb::new_ron(&db_name, DumpPolicy::AutoDump);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Coor {
+        x: i32,
+        y: i32,
+    }
+    let mycoor = Coor { x: 1, y: 2 };
+
+    db.set("num_test", &10).unwrap();
+    db.set("float_test", &2.5).unwrap();
+    db.set("string_test", &String::from("\"quotes\" שָׁלוֹם 😻\n"))
+        .unwrap();
+    db.set("vec_test", &vec![1, 2, 3]).unwrap();
+    db.set("struct", &mycoor).unwrap();
+    db.collection("coords").set("c1", &mycoor).unwrap();
+    db.create_index("coords", "x").unwrap();
+
+    let mut read_db = DocDb::load_ron(&db_name, DumpPolicy::NeverDump).unwrap();
+    assert_eq!(read_db.get::<i32>("num_test").unwrap(), 10);
+    assert_eq!(read_db.get::<f32>("float_test").unwrap(), 2.5);
+    assert_eq!(
+        read_db.get::<String>("string_test").unwrap(),
+        String::from("\"quotes\" שָׁלוֹם 😻\n")
+    );
+    assert_eq!(read_db.get::<Vec<i32>>("vec_test").unwrap(), vec![1, 2, 3]);
+    assert_eq!(read_db.get::<Coor>("struct").unwrap(), mycoor);
+    assert_eq!(
+        read_db.collection("coords").get::<Coor>("c1").unwrap(),
+        mycoor
+    );
+    assert_eq!(read_db.get::<String>("num_test"), None);
+
+    // the file is meant to be edited by hand, values are embedded as RON
+    let content = std::fs::read_to_string(&db_name).unwrap();
+    assert!(content.contains("\"num_test\":10"));
+    assert!(content.contains("\"struct\":(x:1,y:2)"));
+    std::fs::write(
+        &db_name,
+        content.replace("\"num_test\":10", "\"num_test\":11"),
+    )
+    .unwrap();
+    let read_db = DocDb::load_ron(&db_name, DumpPolicy::NeverDump).unwrap();
+    assert_eq!(read_db.get::<i32>("num_test").unwrap(), 11);
+}
+
 #[test]
 fn test_edge_cases() {
     let db_name = "test_edge_cases.db";
@@ -319,3 +508,278 @@ fn test_iter() {
     // verify all 5 keys were seen
     assert_eq!(key_seen.iter().filter(|&t| *t).count(), 5);
 }
+
+#[test]
+fn test_json_file_layout() {
+    let db_name = "test_json_file_layout.db";
+    set_test_src!(db_name);
+
+    let mut db = DocDb::new(db_name, DumpPolicy::AutoDump, SerializationMethod::Json);
+    db.set("num", &100).unwrap();
+    db.set("string", &"my string").unwrap();
+    db.set("struct", &serde_json::json!({"width": 2})).unwrap();
+
+    // values are nested in the file rather than encoded as strings
+    let file: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(db_name).unwrap()).unwrap();
+    assert_eq!(file["data"]["num"], 100);
+    assert_eq!(file["data"]["string"], "my string");
+    assert_eq!(file["data"]["struct"]["width"], 2);
+
+    // files of the previous layouts still load
+    for old_file in [
+        r#"{"version":1,"data":{"num":"100","struct":"{\"width\":2}"},"collections":{}}"#,
+        r#"{"num":"100","struct":"{\"width\":2}"}"#,
+    ] {
+        std::fs::write(db_name, old_file).unwrap();
+        let read_db = DocDb::load_read_only(db_name, SerializationMethod::Json).unwrap();
+        assert_eq!(read_db.get::<i32>("num"), Some(100));
+        assert_eq!(
+            read_db.get::<serde_json::Value>("struct"),
+            Some(serde_json::json!({"width": 2}))
+        );
+    }
+
+    // a legacy file may hold a key named version
+    std::fs::write(db_name, r#"{"version":"100"}"#).unwrap();
+    let read_db = DocDb::load_read_only(db_name, SerializationMethod::Json).unwrap();
+    assert_eq!(read_db.get::<i32>("version"), Some(100));
+
+    // a versioned file with a bad field reports that field
+    std::fs::write(
+        db_name,
+        r#"{"version":2,"data":{},"collections":{},"last_id":"x"}"#,
+    )
+    .unwrap();
+    let err = DocDb::load_read_only(db_name, SerializationMethod::Json)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("expected u64"), "{}", err);
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn test_yaml_file_layout() {
+    let db_name = "test_yaml_file_layout.db";
+    set_test_src!(db_name);
+
+    let mut db = DocDb::new(db_name, DumpPolicy::AutoDump, SerializationMethod::Yaml);
+    db.set("num", &100).unwrap();
+    db.set("string", &"my string").unwrap();
+    db.set("struct", &serde_json::json!({"width": 2})).unwrap();
+
+    let content = std::fs::read_to_string(db_name).unwrap();
+    assert!(content.contains("struct:\n    width: 2\n"));
+
+    // a hand edit
+    std::fs::write(db_name, content.replace("width: 2", "width: 3")).unwrap();
+    let read_db = DocDb::load_read_only(db_name, SerializationMethod::Yaml).unwrap();
+    assert_eq!(read_db.get::<String>("string").unwrap(), "my string");
+    assert_eq!(
+        read_db.get::<serde_json::Value>("struct"),
+        Some(serde_json::json!({"width": 3}))
+    );
+
+    // files of the previous layout still load
+    std::fs::write(
+        db_name,
+        "version: 1\ndata:\n  num: '100'\n  struct: |\n    width: 2\ncollections: {}\n",
+    )
+    .unwrap();
+    let read_db = DocDb::load_read_only(db_name, SerializationMethod::Yaml).unwrap();
+    assert_eq!(read_db.get::<i32>("num"), Some(100));
+    assert_eq!(
+        read_db.get::<serde_json::Value>("struct"),
+        Some(serde_json::json!({"width": 2}))
+    );
+
+    // a versioned file with a bad field reports that field
+    std::fs::write(
+        db_name,
+        "version: 2\ndata: {}\ncollections: {}\nlast_id: x\n",
+    )
+    .unwrap();
+    let err = DocDb::load_read_only(db_name, SerializationMethod::Yaml)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("expected u64"), "{}", err);
+}
+
+fn dump_with_options(db_name: &str, ser_method: SerializationMethod, keys: &[&str]) -> String {
+    let mut db = DocDb::new(db_name, DumpPolicy::DumpRelyRequest, ser_method);
+    db.set_serializer_options(SerializerOptions {
+        pretty: true,
+        sort_keys: true,
+    });
+    for key in keys {
+        let members: std::collections::HashMap<&str, &str> =
+            keys.iter().map(|k| (*k, *key)).collect();
+        db.set(key, &members).unwrap();
+        db.collection(key).set(key, &1).unwrap();
+    }
+    db.dump().unwrap();
+    std::fs::read_to_string(db_name).unwrap()
+}
+
+#[test]
+fn test_pretty_sorted_dump() {
+    let db_name = "test_pretty_sorted_dump.db";
+    set_test_src!(db_name);
+
+    let content = dump_with_options(db_name, SerializationMethod::Json, &["b", "c", "a"]);
+    assert!(content.contains("\n  \"data\": {\n    \"a\": {\n"));
+    let (a, b, c) = (
+        content.find("\"a\":").unwrap(),
+        content.find("\"b\":").unwrap(),
+        content.find("\"c\":").unwrap(),
+    );
+    assert!(a < b && b < c);
+
+    // the same data gives the same file
+    assert_eq!(
+        dump_with_options(db_name, SerializationMethod::Json, &["c", "a", "b"]),
+        content
+    );
+
+    let read_db = DocDb::load_read_only(db_name, SerializationMethod::Json).unwrap();
+    assert_eq!(read_db.total_nums(), 3);
+    assert_eq!(
+        read_db
+            .get::<std::collections::HashMap<String, String>>("a")
+            .unwrap()["b"],
+        "a"
+    );
+}
+
+#[test]
+fn test_serializer_options_are_kept_on_load() {
+    let db_name = "test_serializer_options_load.db";
+    set_test_src!(db_name);
+
+    let content = dump_with_options(db_name, SerializationMethod::Json, &["b", "c", "a"]);
+
+    // the first dump after loading the file writes it the same way
+    let mut db = DocDb::load(db_name, DumpPolicy::AutoDump, SerializationMethod::Json).unwrap();
+    db.set("d", &1).unwrap();
+    db.rem("d").unwrap();
+    assert_eq!(std::fs::read_to_string(db_name).unwrap(), content);
+
+    db.set_serializer_options(SerializerOptions::default());
+    db.dump().unwrap();
+    let mut db = DocDb::load(db_name, DumpPolicy::AutoDump, SerializationMethod::Json).unwrap();
+    db.dump().unwrap();
+    assert!(!std::fs::read_to_string(db_name).unwrap().contains('\n'));
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn test_sorted_yaml_dump() {
+    let db_name = "test_sorted_yaml_dump.db";
+    set_test_src!(db_name);
+
+    let keys = ["k1", "k2", "k3", "k4", "k5", "k6", "k7", "k8"];
+    let content = dump_with_options(db_name, SerializationMethod::Yaml, &keys);
+    assert!(content.contains("  k1:\n    k1: k1\n    k2: k1\n    k3: k1\n"));
+    for _ in 0..3 {
+        assert_eq!(
+            dump_with_options(db_name, SerializationMethod::Yaml, &keys),
+            content
+        );
+    }
+}
+
+#[test]
+fn test_raw_values() {
+    #[allow(unused_mut)]
+    let mut methods = vec![SerializationMethod::Json];
+    #[cfg(feature = "yaml")]
+    methods.push(SerializationMethod::Yaml);
+    #[cfg(feature = "bincode")]
+    methods.push(SerializationMethod::Bin);
+    #[cfg(feature = "toml")]
+    methods.push(SerializationMethod::Toml);
+    #[cfg(feature = "ron")]
+    methods.push(SerializationMethod::Ron);
+    #[cfg(feature = "cbor")]
+    methods.push(SerializationMethod::Cbor);
+    #[cfg(feature = "msgpack")]
+    methods.push(SerializationMethod::MessagePack);
+
+    let blob: &[u8] = &[0xff, 0xfe, 0x00, b'{', 0x80];
+    for ser_method in methods {
+        let db_name = format!("test_raw_values_{}.db", ser_method);
+        set_test_src!(&db_name);
+
+        let mut db = DocDb::new(&db_name, DumpPolicy::AutoDump, ser_method);
+        db.set("num", &10).unwrap();
+        db.set_raw("blob", blob).unwrap();
+        db.set_raw("text", b"10").unwrap();
+        assert_eq!(db.get_raw("blob"), Some(blob));
+        assert!(!db.get_raw("num").unwrap().is_empty());
+
+        let read_db = DocDb::load_read_only(&db_name, ser_method).unwrap();
+        assert_eq!(read_db.get_raw("blob"), Some(blob));
+        assert_eq!(read_db.get_raw("text"), Some(&b"10"[..]));
+        assert_eq!(read_db.get::<i32>("num"), Some(10));
+        let item = read_db
+            .iter()
+            .find(|item| item.get_key() == "blob")
+            .unwrap();
+        assert_eq!(item.get_raw_value(), blob);
+
+        // a raw value can be replaced by a serialized one and removed
+        db.set("text", &"text").unwrap();
+        assert!(db.rem("blob").unwrap());
+        let read_db = DocDb::load_read_only(&db_name, ser_method).unwrap();
+        assert_eq!(read_db.get::<String>("text").unwrap(), "text");
+        assert_eq!(read_db.get_raw("blob"), None);
+    }
+
+    let db_name = "test_raw_values_base64.db";
+    set_test_src!(db_name);
+    let mut db = DocDb::new_json(db_name, DumpPolicy::AutoDump);
+    db.set_raw("blob", blob).unwrap();
+    let file: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(db_name).unwrap()).unwrap();
+    assert_eq!(file["raw"]["blob"], "//4Ae4A=");
+}
+
+#[test]
+fn test_raw_values_with_documents() {
+    let db_name = "test_raw_values_documents.db";
+    set_test_src!(db_name);
+
+    let mut db = DocDb::new_json(db_name, DumpPolicy::AutoDump);
+    db.set("user:1", &serde_json::json!({"name": "ann", "age": 30}))
+        .unwrap();
+    db.set_raw("user:avatar", &[0xff, 0xd8, 0xff]).unwrap();
+
+    // raw values aren't documents, so they are skipped
+    let found = db.find(&docdb::Filter::exists("name")).unwrap();
+    assert_eq!(found.len(), 1);
+    let users = db.query().fetch::<serde_json::Value>().unwrap();
+    assert_eq!(users.len(), 1);
+    assert_eq!(db.aggregate("user:").count(), 1);
+    assert_eq!(db.aggregate("user:").sum("age").unwrap(), 30.0);
+
+    // hooks see raw values as base64 strings
+    db.on_before_set("thumb:", |_key, doc| match doc.as_str() {
+        Some("AAE=") => Err(docdb::error::DocError::Rejected("empty".to_string())),
+        _ => Ok(()),
+    });
+    db.on_before_rem("thumb:", |_key, doc| match doc.as_str() {
+        Some("Ag==") => Err(docdb::error::DocError::Rejected("kept".to_string())),
+        _ => Ok(()),
+    });
+    assert!(db.set_raw("thumb:1", &[0x00, 0x01]).is_err());
+    assert!(!db.exist("thumb:1"));
+    db.set_raw("thumb:2", &[0x02]).unwrap();
+    assert!(db.rem("thumb:2").is_err());
+    assert_eq!(db.get_raw("thumb:2"), Some(&[0x02][..]));
+
+    // raw values can't be checked against a schema
+    db.set_schema("user:", &serde_json::json!({"type": "object"}))
+        .unwrap();
+    assert!(db.set_raw("user:banner", &[0x00]).is_err());
+    assert!(!db.exist("user:banner"));
+}
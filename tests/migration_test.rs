@@ -0,0 +1,108 @@
+use docdb::error::DocError;
+use docdb::{DocDb, DumpPolicy, Migration, SerializationMethod};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+mod common;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct User {
+    first: String,
+    last: String,
+    active: bool,
+}
+
+/// version 1: split `name` into `first` and `last`
+fn split_name(db: &mut DocDb) -> docdb::error::Result<()> {
+    for key in db.get_all_keys() {
+        let mut doc = db.get::<serde_json::Value>(&key).unwrap();
+        let name = doc["name"].as_str().unwrap_or_default().to_string();
+        let (first, last) = name.split_once(' ').unwrap_or((&name, ""));
+        doc = json!({"first": first, "last": last});
+        db.set(&key, &doc)?;
+    }
+    Ok(())
+}
+
+/// version 2: add `active`
+fn add_active(db: &mut DocDb) -> docdb::error::Result<()> {
+    for key in db.get_all_keys() {
+        db.merge_patch(&key, &json!({"active": true}))?;
+    }
+    Ok(())
+}
+
+fn fail(_db: &mut DocDb) -> docdb::error::Result<()> {
+    Err(DocError::Rejected("bad migration".to_string()))
+}
+
+#[test]
+fn test_migrations_run_in_order_once() {
+    set_test_src!("migration_order.db");
+
+    {
+        let mut db = DocDb::new(
+            "migration_order.db",
+            DumpPolicy::AutoDump,
+            SerializationMethod::Json,
+        );
+        db.set("u1", &json!({"name": "Ada Lovelace"})).unwrap();
+    }
+
+    let migrations: [(u64, Migration); 2] = [(2, add_active), (1, split_name)];
+    let db = DocDb::load_with_migrations(
+        "migration_order.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+        &migrations,
+    )
+    .unwrap();
+    assert_eq!(db.schema_version(), 2);
+    let expected = User {
+        first: "Ada".to_string(),
+        last: "Lovelace".to_string(),
+        active: true,
+    };
+    assert_eq!(db.get::<User>("u1").unwrap(), expected);
+    drop(db);
+
+    // the migrations already applied aren't run again
+    let db = DocDb::load_with_migrations(
+        "migration_order.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+        &[(1, fail), (2, fail)],
+    )
+    .unwrap();
+    assert_eq!(db.get::<User>("u1").unwrap(), expected);
+}
+
+#[test]
+fn test_failed_migration_leaves_file_unchanged() {
+    set_test_src!("migration_fail.db");
+
+    {
+        let mut db = DocDb::new(
+            "migration_fail.db",
+            DumpPolicy::AutoDump,
+            SerializationMethod::Json,
+        );
+        db.set("u1", &json!({"name": "Ada Lovelace"})).unwrap();
+        db.set_schema_version(1).unwrap();
+    }
+
+    let res = DocDb::load_with_migrations(
+        "migration_fail.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+        &[(1, split_name), (2, add_active), (3, fail)],
+    );
+    assert!(matches!(res, Err(DocError::Rejected(_))));
+
+    let db = DocDb::load_json("migration_fail.db", DumpPolicy::NeverDump).unwrap();
+    assert_eq!(db.schema_version(), 1);
+    assert_eq!(
+        db.get::<serde_json::Value>("u1").unwrap(),
+        json!({"name": "Ada Lovelace"})
+    );
+}
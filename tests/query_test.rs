@@ -0,0 +1,207 @@
+use docdb::{filter, DocDb, DumpPolicy, Filter, Order, SerializationMethod};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+mod common;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Address {
+    city: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Person {
+    name: String,
+    age: u32,
+    address: Address,
+    tags: Vec<String>,
+}
+
+fn person(name: &str, age: u32, city: &str, tags: &[&str]) -> Person {
+    Person {
+        name: name.to_string(),
+        age,
+        address: Address {
+            city: city.to_string(),
+        },
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+    }
+}
+
+fn names(items: Vec<docdb::DocDbIteratorItem>) -> Vec<String> {
+    let mut names: Vec<String> = items
+        .iter()
+        .map(|kv| kv.get_value::<Person>().unwrap().name)
+        .collect();
+    names.sort();
+    names
+}
+
+fn fill(db: &mut DocDb) {
+    let mut people = db.collection("people");
+    people
+        .insert_many(&[
+            person("anna", 25, "Berlin", &["admin"]),
+            person("ben", 35, "Berlin", &[]),
+            person("carl", 45, "Paris", &["admin", "dev"]),
+            person("dora", 31, "Rome", &["dev"]),
+        ])
+        .unwrap();
+}
+
+#[test]
+fn test_find_with_filter_macro() {
+    set_test_src!("query_macro.db");
+
+    let mut db = DocDb::new(
+        "query_macro.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    fill(&mut db);
+    let people = db.collection("people");
+
+    let found = people
+        .find(&filter!(age > 30 && address.city == "Berlin"))
+        .unwrap();
+    assert_eq!(names(found), vec!["ben"]);
+
+    let min_age = 40;
+    let found = people
+        .find(&filter!(age >= min_age || !(address.city != "Rome")))
+        .unwrap();
+    assert_eq!(names(found), vec!["carl", "dora"]);
+
+    // an array field matches if any element does
+    let found = people.find(&filter!(tags == "admin")).unwrap();
+    assert_eq!(names(found), vec!["anna", "carl"]);
+
+    let found = people
+        .find(&Filter::is_in("address.city", vec!["Paris", "Rome"]))
+        .unwrap();
+    assert_eq!(names(found), vec!["carl", "dora"]);
+}
+
+#[test]
+fn test_find_with_json_filter() {
+    set_test_src!("query_json.db");
+
+    let mut db = DocDb::new(
+        "query_json.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    fill(&mut db);
+    db.set("top", &person("eve", 50, "Berlin", &[])).unwrap();
+
+    let filter = Filter::from_json(&json!({
+        "address.city": "Berlin",
+        "age": {"$gte": 30, "$lt": 60}
+    }))
+    .unwrap();
+    assert_eq!(
+        filter,
+        filter!(address.city == "Berlin" && age >= 30 && age < 60)
+    );
+
+    // top-level keys and collections are searched separately
+    assert_eq!(names(db.find(&filter).unwrap()), vec!["eve"]);
+    assert_eq!(
+        names(db.collection("people").find(&filter).unwrap()),
+        vec!["ben"]
+    );
+
+    let filter = Filter::from_json(&json!({
+        "$or": [{"tags": {"$exists": false}}, {"tags.1": "dev"}]
+    }))
+    .unwrap();
+    assert_eq!(
+        names(db.collection("people").find(&filter).unwrap()),
+        vec!["carl"]
+    );
+
+    let err = Filter::from_json(&json!({"age": {"$between": [1, 2]}})).unwrap_err();
+    assert!(matches!(err.get_type(), docdb::error::ErrorType::Query));
+}
+
+#[derive(Deserialize, Debug)]
+struct NameAndCity {
+    name: String,
+    address: Address,
+}
+
+#[test]
+fn test_query_sort_paginate_project() {
+    set_test_src!("query_sort.db");
+
+    let mut db = DocDb::new(
+        "query_sort.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    fill(&mut db);
+    db.collection("people")
+        .set(
+            "eve",
+            &json!({"name": "eve", "address": {"city": "Berlin"}}),
+        )
+        .unwrap();
+
+    let people = db.collection("people");
+    let oldest: Vec<String> = people
+        .query()
+        .sort_by("age", Order::Desc)
+        .limit(2)
+        .fetch::<Person>()
+        .unwrap()
+        .into_iter()
+        .map(|(_, p)| p.name)
+        .collect();
+    assert_eq!(oldest, vec!["carl", "ben"]);
+
+    // documents without the field sort first, ties are broken by the next field
+    let rows = people
+        .query()
+        .filter(filter!(address.city == "Berlin"))
+        .sort_by("age", Order::Asc)
+        .project(&["name", "address.city"])
+        .fetch::<NameAndCity>()
+        .unwrap();
+    let names: Vec<&str> = rows.iter().map(|(_, row)| row.name.as_str()).collect();
+    assert_eq!(names, vec!["eve", "anna", "ben"]);
+    assert!(rows.iter().all(|(_, row)| row.address.city == "Berlin"));
+
+    let rows = people
+        .query()
+        .sort_by("address.city", Order::Asc)
+        .sort_by("name", Order::Desc)
+        .skip(1)
+        .limit(3)
+        .project(&["name"])
+        .fetch::<serde_json::Value>()
+        .unwrap();
+    let docs: Vec<serde_json::Value> = rows.into_iter().map(|(_, doc)| doc).collect();
+    assert_eq!(
+        docs,
+        vec![
+            json!({"name": "ben"}),
+            json!({"name": "anna"}),
+            json!({"name": "carl"})
+        ]
+    );
+}
+
+#[cfg(feature = "bincode")]
+#[test]
+fn test_find_requires_self_describing_values() {
+    set_test_src!("query_bincode.db");
+
+    let mut db = DocDb::new(
+        "query_bincode.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Bin,
+    );
+    db.set("anna", &person("anna", 25, "Berlin", &[])).unwrap();
+
+    assert!(db.find(&filter!(age > 1)).is_err());
+}
@@ -0,0 +1,136 @@
+use docdb::error::DocError;
+use docdb::{DocDb, DumpPolicy, SerializationMethod};
+use serde_json::json;
+
+mod common;
+
+fn violation_path(res: docdb::error::Result<()>) -> String {
+    match res {
+        Err(DocError::SchemaViolation { path, .. }) => path,
+        Err(err) => panic!("unexpected error {}", err),
+        Ok(_) => panic!("the write should be rejected"),
+    }
+}
+
+#[test]
+fn test_schema_rejects_bad_documents() {
+    set_test_src!("schema_reject.db");
+
+    let mut db = DocDb::new(
+        "schema_reject.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    db.set_schema(
+        "user:",
+        &json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "roles": {"type": "array", "items": {"enum": ["admin", "dev"]}, "uniqueItems": true},
+                "address": {
+                    "type": "object",
+                    "properties": {"zip": {"type": ["string", "null"]}},
+                    "additionalProperties": false
+                }
+            }
+        }),
+    )
+    .unwrap();
+
+    db.set(
+        "user:1",
+        &json!({"name": "ann", "age": 30, "roles": ["dev"]}),
+    )
+    .unwrap();
+    assert_eq!(
+        violation_path(db.set("user:2", &json!({"age": 3}))),
+        "/name"
+    );
+    assert_eq!(
+        violation_path(db.set("user:2", &json!({"name": "bob", "age": -1}))),
+        "/age"
+    );
+    assert_eq!(
+        violation_path(db.set("user:2", &json!({"name": "bob", "roles": ["dev", "ops"]}))),
+        "/roles/1"
+    );
+    assert_eq!(
+        violation_path(db.set("user:2", &json!({"name": "bob", "address": {"zip": 1}}))),
+        "/address/zip"
+    );
+    assert_eq!(
+        violation_path(db.set("user:2", &json!({"name": "bob", "address": {"city": "x"}}))),
+        "/address/city"
+    );
+    assert_eq!(violation_path(db.set("user:2", &"bob")), "");
+    assert!(!db.exist("user:2"));
+
+    // keys outside the prefix aren't checked, and patches are
+    db.set("other", &"anything").unwrap();
+    assert!(db.set_path("user:1", "/age", &"old").is_err());
+    assert_eq!(db.get_path::<u32>("user:1", "/age"), Some(30));
+}
+
+#[test]
+fn test_schema_with_unsupported_keywords_is_rejected() {
+    set_test_src!("schema_unsupported.db");
+
+    let mut db = DocDb::new(
+        "schema_unsupported.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    for schema in [
+        json!({"type": "string", "pattern": "^a"}),
+        json!({"properties": {"email": {"type": "string", "format": "email"}}}),
+        json!({"items": {"$ref": "#/$defs/item"}}),
+        json!({"anyOf": [{"type": "null"}, {"patternProperties": {"^x": true}}]}),
+        json!({"not": {"additionalProperties": {"if": {"type": "string"}}}}),
+    ] {
+        assert!(matches!(
+            db.set_schema("user:", &schema),
+            Err(DocError::Query(_))
+        ));
+    }
+    assert!(matches!(
+        db.collection("users")
+            .set_schema("", &json!({"pattern": "^a"})),
+        Err(DocError::Query(_))
+    ));
+
+    // nothing was attached
+    db.set("user:1", &json!({"email": 1})).unwrap();
+    db.collection("users").set("1", &"bob").unwrap();
+}
+
+#[test]
+fn test_collection_schema_is_persisted() {
+    set_test_src!("schema_persist.db");
+
+    {
+        let mut db = DocDb::new(
+            "schema_persist.db",
+            DumpPolicy::AutoDump,
+            SerializationMethod::Json,
+        );
+        let mut orders = db.collection("orders");
+        orders
+            .set_schema("", &json!({"properties": {"total": {"type": "number"}}}))
+            .unwrap();
+        assert!(orders.insert(&json!({"total": "ten"})).is_err());
+        assert_eq!(orders.total_nums(), 0);
+        assert!(db.set_schema("x", &json!(1)).is_err());
+    }
+
+    let mut db = DocDb::load_json("schema_persist.db", DumpPolicy::AutoDump).unwrap();
+    let mut orders = db.collection("orders");
+    assert!(orders.set("a", &json!({"total": "ten"})).is_err());
+    orders.set("a", &json!({"total": 10})).unwrap();
+
+    assert!(orders.remove_schema("").unwrap());
+    assert!(!orders.remove_schema("").unwrap());
+    orders.set("a", &json!({"total": "ten"})).unwrap();
+}
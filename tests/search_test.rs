@@ -0,0 +1,83 @@
+use docdb::{DocDb, DumpPolicy, SerializationMethod};
+use serde_json::json;
+
+mod common;
+
+#[test]
+fn test_search_ranks_matches() {
+    set_test_src!("search_rank.db");
+
+    let mut db = DocDb::new(
+        "search_rank.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    let mut notes = db.collection("notes");
+    notes
+        .set(
+            "fox",
+            &json!({"title": "The Quick Brown Fox", "body": "jumps over the dog"}),
+        )
+        .unwrap();
+    notes
+        .set(
+            "brown",
+            &json!({"title": "Brown bread", "tags": ["baking", "brown"]}),
+        )
+        .unwrap();
+    notes
+        .set(
+            "slow",
+            &json!({"title": "A slow turtle", "body": "quick? no."}),
+        )
+        .unwrap();
+    db.create_text_index("notes", &["title", "tags"]).unwrap();
+
+    // "brown" occurs twice in the second note, but the first one also holds "quick"
+    assert_eq!(db.search("notes", "brown").unwrap(), vec!["brown", "fox"]);
+    assert_eq!(
+        db.search("notes", "QUICK brown").unwrap(),
+        vec!["fox", "brown"]
+    );
+    assert_eq!(db.search("notes", "turtle").unwrap(), vec!["slow"]);
+    assert!(db
+        .search("notes", "bread-baking")
+        .unwrap()
+        .contains(&"brown".to_string()));
+    assert!(db.search("notes", "missing").unwrap().is_empty());
+    assert!(db.search("other", "quick").is_err());
+
+    // writes keep the index up to date
+    db.collection("notes").rem("brown").unwrap();
+    db.collection("notes")
+        .set("slow", &json!({"title": "A quick turtle"}))
+        .unwrap();
+    assert_eq!(db.search("notes", "brown").unwrap(), vec!["fox"]);
+    let mut quick = db.search("notes", "quick").unwrap();
+    quick.sort();
+    assert_eq!(quick, vec!["fox", "slow"]);
+}
+
+#[test]
+fn test_text_index_is_rebuilt_on_load() {
+    set_test_src!("search_load.db");
+
+    {
+        let mut db = DocDb::new(
+            "search_load.db",
+            DumpPolicy::AutoDump,
+            SerializationMethod::Json,
+        );
+        db.create_text_index("tickets", &["summary"]).unwrap();
+        db.collection("tickets")
+            .insert(&json!({"summary": "Login page crashes"}))
+            .unwrap();
+    }
+
+    let mut db = DocDb::load_json("search_load.db", DumpPolicy::AutoDump).unwrap();
+    assert_eq!(db.search("tickets", "crashes").unwrap().len(), 1);
+
+    assert!(db.drop_text_index("tickets").unwrap());
+    assert!(!db.drop_text_index("tickets").unwrap());
+    assert!(db.search("tickets", "crashes").is_err());
+}
@@ -0,0 +1,101 @@
+use docdb::error::{DocError, Result};
+use docdb::{DocDb, DocSerializer, DumpPolicy};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+mod common;
+
+/// JSON, written as hex digits
+struct HexJson;
+
+impl DocSerializer for HexJson {
+    fn serialize_data(&self, val: &Value) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec(val)?;
+        Ok(json
+            .iter()
+            .flat_map(|b| format!("{:02x}", b).into_bytes())
+            .collect())
+    }
+
+    fn deserialize_data(&self, ser_data: &[u8]) -> Result<Value> {
+        let hex = std::str::from_utf8(ser_data)?;
+        let json = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<std::result::Result<Vec<u8>, _>>()
+            .map_err(|err| DocError::Deserialization(err.to_string()))?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+/// JSON that refuses negative numbers
+struct Unsigned;
+
+impl DocSerializer for Unsigned {
+    fn serialize_data(&self, val: &Value) -> Result<Vec<u8>> {
+        if val.as_i64().is_some_and(|n| n < 0) {
+            return Err(DocError::Serialization("negative number".to_string()));
+        }
+        Ok(serde_json::to_vec(val)?)
+    }
+
+    fn deserialize_data(&self, ser_data: &[u8]) -> Result<Value> {
+        serde_json::from_slice(ser_data).map_err(|err| DocError::Deserialization(err.to_string()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Coor {
+    x: i32,
+    y: i32,
+}
+
+#[test]
+fn test_custom_serializer_round_trip() {
+    let db_name = "test_custom_serializer.db";
+    set_test_src!(db_name);
+
+    let mut db = DocDb::with_serializer(db_name, DumpPolicy::AutoDump, Box::new(HexJson));
+    db.set("num", &10).unwrap();
+    db.set("string", &"my string").unwrap();
+    db.set("struct", &Coor { x: 1, y: 2 }).unwrap();
+    db.collection("coords")
+        .set("c1", &Coor { x: 3, y: 4 })
+        .unwrap();
+
+    let content = std::fs::read(db_name).unwrap();
+    assert!(content.iter().all(u8::is_ascii_hexdigit));
+    let file = HexJson.deserialize_db(&content).unwrap();
+    assert_eq!(file["data"]["struct"], json!({"x": 1, "y": 2}));
+
+    let mut read_db =
+        DocDb::load_with_serializer(db_name, DumpPolicy::NeverDump, Box::new(HexJson)).unwrap();
+    assert_eq!(read_db.get::<i32>("num"), Some(10));
+    assert_eq!(read_db.get::<String>("string").unwrap(), "my string");
+    assert_eq!(read_db.get::<Coor>("struct"), Some(Coor { x: 1, y: 2 }));
+    assert_eq!(
+        read_db.collection("coords").get::<Coor>("c1"),
+        Some(Coor { x: 3, y: 4 })
+    );
+}
+
+#[test]
+fn test_custom_serializer_errors() {
+    let db_name = "test_custom_serializer_errors.db";
+    set_test_src!(db_name);
+
+    let mut db = DocDb::with_serializer(db_name, DumpPolicy::AutoDump, Box::new(Unsigned));
+    db.set("num", &1).unwrap();
+    assert!(matches!(
+        db.set("num", &-1),
+        Err(DocError::Serialization(_))
+    ));
+    assert_eq!(db.get::<i32>("num"), Some(1));
+
+    // a file in another format
+    std::fs::write(db_name, "num: 1").unwrap();
+    assert!(matches!(
+        DocDb::load_with_serializer(db_name, DumpPolicy::NeverDump, Box::new(Unsigned)),
+        Err(DocError::Deserialization(_))
+    ));
+}
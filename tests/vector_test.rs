@@ -0,0 +1,102 @@
+use docdb::{DocDb, DumpPolicy, Metric, SerializationMethod};
+use serde_json::json;
+
+mod common;
+
+fn keys(results: Vec<(String, f32)>) -> Vec<String> {
+    results.into_iter().map(|(key, _)| key).collect()
+}
+
+#[test]
+fn test_nearest_brute_force() {
+    set_test_src!("vector_brute.db");
+
+    let mut db = DocDb::new(
+        "vector_brute.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    let mut docs = db.collection("docs");
+    docs.set("x", &json!({"embedding": [1.0, 0.0]})).unwrap();
+    docs.set("y", &json!({"embedding": [0.0, 1.0]})).unwrap();
+    docs.set("long_x", &json!({"embedding": [3.0, 0.5]}))
+        .unwrap();
+    docs.set("other_dims", &json!({"embedding": [1.0, 0.0, 0.0]}))
+        .unwrap();
+    docs.set("no_embedding", &json!({"text": "hi"})).unwrap();
+
+    let query = [1.0, 0.0];
+    let cosine = db
+        .nearest("docs", "embedding", 2, &query, Metric::Cosine)
+        .unwrap();
+    assert_eq!(keys(cosine.clone()), vec!["x", "long_x"]);
+    assert!(cosine[0].1 > 0.99);
+
+    // dot favors long vectors
+    let dot = db
+        .nearest("docs", "embedding", 1, &query, Metric::Dot)
+        .unwrap();
+    assert_eq!(keys(dot), vec!["long_x"]);
+
+    let l2 = db
+        .nearest("docs", "embedding", 10, &query, Metric::L2)
+        .unwrap();
+    assert_eq!(keys(l2), vec!["x", "y", "long_x"]);
+
+    assert!(db.nearest("docs", "embedding", 1, &[], Metric::L2).is_err());
+    assert!(db
+        .nearest_approx("docs", "embedding", 1, &query, Metric::L2)
+        .is_err());
+}
+
+#[test]
+fn test_nearest_with_vector_index() {
+    set_test_src!("vector_index.db");
+
+    {
+        let mut db = DocDb::new(
+            "vector_index.db",
+            DumpPolicy::AutoDump,
+            SerializationMethod::Json,
+        );
+        let points: Vec<serde_json::Value> = (0..40)
+            .map(|i| {
+                let (cx, cy) = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0), (10.0, 10.0)][i % 4];
+                let jitter = (i / 4) as f32 * 0.1;
+                json!({"id": i, "v": [cx + jitter, cy - jitter]})
+            })
+            .collect();
+        db.collection("points").insert_many(&points).unwrap();
+        db.create_vector_index("points", "v", 4, 1).unwrap();
+
+        // with a single probe, only the cluster around the query is searched
+        let near = db
+            .nearest_approx("points", "v", 3, &[10.0, 10.0], Metric::L2)
+            .unwrap();
+        let exact = db
+            .nearest("points", "v", 3, &[10.0, 10.0], Metric::L2)
+            .unwrap();
+        assert_eq!(near, exact);
+    }
+
+    // the index is rebuilt on load and kept up to date by writes
+    let mut db = DocDb::load_json("vector_index.db", DumpPolicy::AutoDump).unwrap();
+    db.collection("points")
+        .set("new", &json!({"v": [10.0, 9.99]}))
+        .unwrap();
+    let near = db
+        .nearest_approx("points", "v", 1, &[10.0, 9.99], Metric::L2)
+        .unwrap();
+    assert_eq!(keys(near), vec!["new"]);
+
+    db.collection("points").rem("new").unwrap();
+    let near = db
+        .nearest_approx("points", "v", 1, &[10.0, 9.99], Metric::L2)
+        .unwrap();
+    assert_ne!(keys(near), vec!["new"]);
+
+    assert!(db.drop_vector_index("points", "v").unwrap());
+    assert!(db
+        .nearest_approx("points", "v", 1, &[10.0, 10.0], Metric::L2)
+        .is_err());
+}
@@ -0,0 +1,67 @@
+use docdb::{ChangeKind, DocDb, DumpPolicy, SerializationMethod};
+
+mod common;
+
+#[test]
+fn test_subscribe_to_prefix() {
+    set_test_src!("watch_prefix.db");
+
+    let mut db = DocDb::new(
+        "watch_prefix.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    let config = db.subscribe("config.");
+
+    db.set("config.port", &80).unwrap();
+    db.set("other", &1).unwrap();
+    db.set("config.port", &8080).unwrap();
+    db.rem("config.port").unwrap();
+    db.rem("config.missing").unwrap();
+
+    let changes: Vec<_> = config.try_iter().collect();
+    assert_eq!(changes.len(), 3);
+
+    assert_eq!(changes[0].key, "config.port");
+    assert_eq!(changes[0].kind, ChangeKind::Set);
+    assert_eq!(changes[0].get_old::<u16>(), None);
+    assert_eq!(changes[0].get_new::<u16>(), Some(80));
+
+    assert_eq!(changes[1].get_old::<u16>(), Some(80));
+    assert_eq!(changes[1].get_new::<u16>(), Some(8080));
+
+    assert_eq!(changes[2].kind, ChangeKind::Removed);
+    assert_eq!(changes[2].get_old::<u16>(), Some(8080));
+    assert_eq!(changes[2].get_new::<u16>(), None);
+}
+
+#[test]
+fn test_subscribe_to_collection() {
+    set_test_src!("watch_coll.db");
+
+    let mut db = DocDb::new(
+        "watch_coll.db",
+        DumpPolicy::AutoDump,
+        SerializationMethod::Json,
+    );
+    let notes = db.collection("notes").subscribe("");
+    let top_level = db.subscribe("");
+
+    let id = db.collection("notes").insert(&"hello").unwrap();
+    db.collection("notes").set("pinned", &"hi").unwrap();
+    db.collection("notes").clear().unwrap();
+
+    let changes: Vec<_> = notes.try_iter().collect();
+    let kinds: Vec<_> = changes.iter().map(|c| c.kind).collect();
+    assert_eq!(kinds[..2], [ChangeKind::Set, ChangeKind::Set]);
+    assert_eq!(changes[0].key, id.key());
+    assert_eq!(changes[0].collection.as_deref(), Some("notes"));
+    let mut removed: Vec<_> = changes[2..].iter().map(|c| c.key.clone()).collect();
+    removed.sort();
+    assert_eq!(removed, vec![id.key(), "pinned".to_string()]);
+    assert!(top_level.try_recv().is_err());
+
+    // a dropped receiver ends the subscription
+    drop(notes);
+    db.collection("notes").set("x", &1).unwrap();
+}